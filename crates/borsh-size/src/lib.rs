@@ -3,15 +3,18 @@
 ///
 // This isn't the same as the version in bonfida-utils, which assumes all
 // elements of Vec<T> have the same borsh size.
-use near_sdk::{
-    borsh::{BorshDeserialize, BorshSerialize},
-    StorageUsage,
-};
+use borsh::{BorshDeserialize, BorshSerialize};
 use std::{
     collections::{HashMap, HashSet},
     hash::Hash,
 };
 
+#[cfg(feature = "near")]
+pub mod near_collections_size;
+
+/// Same as `near_sdk::StorageUsage`, so the crate builds without `near_sdk`.
+pub type StorageUsage = u64;
+
 /// The overhead to store a string with Borsh. Borsh serializes Strings as
 /// byte slices. Byte slices are serialized with a size prefix, followed by
 /// the bytes.
@@ -51,6 +54,19 @@ pub trait BorshSize: BorshDeserialize + BorshSerialize {
     fn borsh_size(&self) -> StorageUsage;
 }
 
+// Numeric aliases used by the DEX are covered by the impls for the primitives
+// they resolve to:
+//
+// - u32: `ClientId`
+// - u64: `LotBalance`, `SequenceNumber`, `StorageUsage`
+// - u128: `Balance`
+
+impl BorshSize for u32 {
+    fn borsh_size(&self) -> StorageUsage {
+        4
+    }
+}
+
 impl BorshSize for u64 {
     fn borsh_size(&self) -> StorageUsage {
         8
@@ -109,3 +125,26 @@ where
         }
     }
 }
+
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use near_sdk::Balance;
+    use tonic_sdk_dex_types::{LotBalance, SequenceNumber};
+
+    use super::*;
+
+    fn assert_matches_borsh<T: BorshSize>(value: T, expected: StorageUsage) {
+        assert_eq!(value.borsh_size(), expected);
+        assert_eq!(value.try_to_vec().unwrap().len() as StorageUsage, expected);
+    }
+
+    #[test]
+    fn numeric_aliases() {
+        // ClientId
+        assert_matches_borsh::<u32>(7, 4);
+        assert_matches_borsh::<LotBalance>(7, 8);
+        assert_matches_borsh::<SequenceNumber>(7, 8);
+        assert_matches_borsh::<StorageUsage>(7, 8);
+        assert_matches_borsh::<Balance>(7, 16);
+    }
+}
@@ -0,0 +1,119 @@
+/// Estimates of the storage used by inserting an entry into the collections in
+/// `near_sdk::collections`. Unlike the `std` collections, these don't store
+/// their entries in a single borsh blob. Each entry is a separate trie record,
+/// keyed by the collection's prefix, and each record costs a fixed overhead on
+/// top of its key and value.
+use near_sdk::StorageUsage;
+
+use crate::BorshSize;
+
+/// Fixed storage cost of every trie record, in addition to the key and value.
+/// This is `num_extra_bytes_record` in the NEAR runtime config.
+pub const STORAGE_RECORD_OVERHEAD: StorageUsage = 40;
+
+/// Size of the index suffix on keys of [Vector](near_sdk::collections::Vector)
+/// elements. Elements are keyed by `prefix ++ index.to_le_bytes()`.
+pub const VECTOR_INDEX_SIZE: StorageUsage = 8;
+
+/// Size of the byte appended to an
+/// [UnorderedMap](near_sdk::collections::UnorderedMap)'s prefix to build the
+/// prefixes of its internal collections (`i`, `k`, `v`).
+pub const UNORDERED_MAP_SUBPREFIX_SIZE: StorageUsage = 1;
+
+/// Storage used by a single trie record.
+pub fn record_size(key_size: StorageUsage, value_size: StorageUsage) -> StorageUsage {
+    STORAGE_RECORD_OVERHEAD + key_size + value_size
+}
+
+/// Storage used by inserting a new key into a
+/// [LookupMap](near_sdk::collections::LookupMap). Entries are stored as
+/// one record:
+///
+/// `prefix ++ K => V`
+pub fn lookup_map_entry_size<K: BorshSize, V: BorshSize>(
+    prefix_len: StorageUsage,
+    key: &K,
+    value: &V,
+) -> StorageUsage {
+    record_size(prefix_len + key.borsh_size(), value.borsh_size())
+}
+
+/// Storage used by pushing an element onto a
+/// [Vector](near_sdk::collections::Vector). Elements are stored as one
+/// record:
+///
+/// `prefix ++ index => T`
+pub fn vector_entry_size<T: BorshSize>(prefix_len: StorageUsage, value: &T) -> StorageUsage {
+    record_size(prefix_len + VECTOR_INDEX_SIZE, value.borsh_size())
+}
+
+/// Storage used by inserting a new key into an
+/// [UnorderedMap](near_sdk::collections::UnorderedMap). Entries are stored
+/// as three records: a lookup from key to index, and an element in each of
+/// the key and value vectors.
+///
+/// ```text
+/// prefix ++ 'i' ++ K => index
+/// prefix ++ 'k' ++ index => K
+/// prefix ++ 'v' ++ index => V
+/// ```
+pub fn unordered_map_entry_size<K: BorshSize, V: BorshSize>(
+    prefix_len: StorageUsage,
+    key: &K,
+    value: &V,
+) -> StorageUsage {
+    let subprefix_len = prefix_len + UNORDERED_MAP_SUBPREFIX_SIZE;
+    lookup_map_entry_size(subprefix_len, key, &0u64)
+        + vector_entry_size(subprefix_len, key)
+        + vector_entry_size(subprefix_len, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::collections::{LookupMap, UnorderedMap, Vector};
+    use near_sdk::env;
+
+    use super::*;
+
+    fn measure<F: FnOnce()>(f: F) -> StorageUsage {
+        let before = env::storage_usage();
+        f();
+        env::storage_usage() - before
+    }
+
+    #[test]
+    fn lookup_map() {
+        let mut map: LookupMap<String, u128> = LookupMap::new(b"m".to_vec());
+        let key = "alice.near".to_string();
+        let actual = measure(|| {
+            map.insert(&key, &5);
+        });
+        assert_eq!(lookup_map_entry_size(1, &key, &5u128), actual);
+    }
+
+    #[test]
+    fn vector() {
+        let mut vec: Vector<Vec<u64>> = Vector::new(b"vec".to_vec());
+        let value = vec![1, 2, 3];
+        let actual = measure(|| vec.push(&value));
+        assert_eq!(vector_entry_size(3, &value), actual);
+    }
+
+    #[test]
+    fn unordered_map() {
+        let mut map: UnorderedMap<String, Vec<String>> = UnorderedMap::new(b"um".to_vec());
+        let key = "bob.near".to_string();
+        let value = vec!["a".to_string(), "bc".to_string()];
+        let actual = measure(|| {
+            map.insert(&key, &value);
+        });
+        assert_eq!(unordered_map_entry_size(2, &key, &value), actual);
+
+        // a second key costs the same as the first for same-sized entries
+        let key = "eve.near".to_string();
+        let actual = measure(|| {
+            map.insert(&key, &value);
+        });
+        assert_eq!(unordered_map_entry_size(2, &key, &value), actual);
+    }
+}
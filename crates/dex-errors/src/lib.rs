@@ -1,11 +1,12 @@
-/// This file contains error messages for normal runtime errors. These messages
-/// can be parsed by user-facing clients to show friendly error messages.
+//! This file contains error messages for normal runtime errors. These messages
+//! can be parsed by user-facing clients to show friendly error messages.
 
 /////////////////////////////
 // miscellaneous errors (E0X)
 /////////////////////////////
 pub const INVALID_TOKEN_ID: &str = "E01: invalid token ID";
 pub const INVALID_ACTION: &str = "E02: Invalid batch action";
+pub const INVALID_MARKET_ID: &str = "E03: invalid market ID";
 
 ///////////////////////
 // account errors (E1X)
@@ -23,6 +24,9 @@ pub const EXCEEDED_ORDER_LIMIT: &str = "E23: exceeded order limit";
 pub const ORDER_NOT_FOUND: &str = "E24: order not found";
 pub const EXCEEDED_SLIPPAGE_TOLERANCE: &str = "E25: exceeded slippage tolerance";
 pub const SELF_TRADE: &str = "E26: order would self trade";
+pub const ZERO_LIMIT_PRICE: &str = "E27: zero limit price";
+pub const PRICE_OVERFLOW: &str = "E28: price overflow";
+pub const DUPLICATE_CLIENT_ID: &str = "E29: duplicate client ID";
 
 ///////////////////////////////
 // market creation errors (E3X)
@@ -31,3 +35,141 @@ pub const MARKET_EXISTS: &str = "E31: market exists";
 pub const INVALID_QUOTE_LOT_SIZE: &str = "E32: invalid quote lot size";
 pub const INVALID_BASE_LOT_SIZE: &str = "E33: invalid base lot size";
 pub const INSUFFICIENT_MARKET_DEPOSIT: &str = "E34: insufficient market deposit";
+
+/// Typed form of the error messages above, eg, for asserting on errors in
+/// client-side tests. Convert a message back with [match_error].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DexError {
+    InvalidTokenId,
+    InvalidAction,
+    InvalidMarketId,
+    InsufficientBalance,
+    InsufficientStorageBalance,
+    AccountNotFound,
+    MissingLimitPrice,
+    ZeroOrderAmount,
+    ExceededOrderLimit,
+    OrderNotFound,
+    ExceededSlippageTolerance,
+    SelfTrade,
+    ZeroLimitPrice,
+    PriceOverflow,
+    DuplicateClientId,
+    MarketExists,
+    InvalidQuoteLotSize,
+    InvalidBaseLotSize,
+    InsufficientMarketDeposit,
+}
+
+impl DexError {
+    pub fn all() -> [DexError; 19] {
+        use DexError::*;
+        [
+            InvalidTokenId,
+            InvalidAction,
+            InvalidMarketId,
+            InsufficientBalance,
+            InsufficientStorageBalance,
+            AccountNotFound,
+            MissingLimitPrice,
+            ZeroOrderAmount,
+            ExceededOrderLimit,
+            OrderNotFound,
+            ExceededSlippageTolerance,
+            SelfTrade,
+            ZeroLimitPrice,
+            PriceOverflow,
+            DuplicateClientId,
+            MarketExists,
+            InvalidQuoteLotSize,
+            InvalidBaseLotSize,
+            InsufficientMarketDeposit,
+        ]
+    }
+
+    /// The error message, eg, `"E24: order not found"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DexError::InvalidTokenId => INVALID_TOKEN_ID,
+            DexError::InvalidAction => INVALID_ACTION,
+            DexError::InvalidMarketId => INVALID_MARKET_ID,
+            DexError::InsufficientBalance => INSUFFICIENT_BALANCE,
+            DexError::InsufficientStorageBalance => INSUFFICIENT_STORAGE_BALANCE,
+            DexError::AccountNotFound => ACCOUNT_NOT_FOUND,
+            DexError::MissingLimitPrice => MISSING_LIMIT_PRICE,
+            DexError::ZeroOrderAmount => ZERO_ORDER_AMOUNT,
+            DexError::ExceededOrderLimit => EXCEEDED_ORDER_LIMIT,
+            DexError::OrderNotFound => ORDER_NOT_FOUND,
+            DexError::ExceededSlippageTolerance => EXCEEDED_SLIPPAGE_TOLERANCE,
+            DexError::SelfTrade => SELF_TRADE,
+            DexError::ZeroLimitPrice => ZERO_LIMIT_PRICE,
+            DexError::PriceOverflow => PRICE_OVERFLOW,
+            DexError::DuplicateClientId => DUPLICATE_CLIENT_ID,
+            DexError::MarketExists => MARKET_EXISTS,
+            DexError::InvalidQuoteLotSize => INVALID_QUOTE_LOT_SIZE,
+            DexError::InvalidBaseLotSize => INVALID_BASE_LOT_SIZE,
+            DexError::InsufficientMarketDeposit => INSUFFICIENT_MARKET_DEPOSIT,
+        }
+    }
+
+    /// The code prefix of the message, eg, `"E24"`.
+    pub fn code(&self) -> &'static str {
+        &self.as_str()[..3]
+    }
+}
+
+impl std::fmt::Display for DexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parse the error code out of a panic message. The code may be preceded by
+/// text added by the runtime, eg, `"Smart contract panicked: E24: order not
+/// found"`. Returns [None] if the message has no known code.
+pub fn match_error(panic_message: &str) -> Option<DexError> {
+    let bytes = panic_message.as_bytes();
+    (0..bytes.len().saturating_sub(3))
+        .filter(|&i| {
+            bytes[i] == b'E'
+                && bytes[i + 1].is_ascii_digit()
+                && bytes[i + 2].is_ascii_digit()
+                && bytes[i + 3] == b':'
+        })
+        .find_map(|i| {
+            let code = &panic_message[i..i + 3];
+            DexError::all().iter().copied().find(|e| e.code() == code)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_error_round_trip() {
+        for error in DexError::all().iter().copied() {
+            assert_eq!(match_error(error.as_str()), Some(error));
+            let message = format!("Smart contract panicked: {}", error);
+            assert_eq!(match_error(&message), Some(error));
+        }
+    }
+
+    #[test]
+    fn codes_are_unique() {
+        let all = DexError::all();
+        for (i, a) in all.iter().enumerate() {
+            for b in &all[i + 1..] {
+                assert_ne!(a.code(), b.code());
+            }
+        }
+    }
+
+    #[test]
+    fn match_error_unknown() {
+        assert_eq!(match_error(""), None);
+        assert_eq!(match_error("E24"), None);
+        assert_eq!(match_error("E99: unknown"), None);
+        assert_eq!(match_error("something went wrong"), None);
+    }
+}
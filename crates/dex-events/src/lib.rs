@@ -1,6 +1,8 @@
 use std::fmt;
 
 use near_sdk::json_types::U128;
+#[cfg(feature = "timestamped_events")]
+use near_sdk::json_types::U64;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{env, AccountId};
 
@@ -11,6 +13,23 @@ use tonic_sdk_dex_types::*;
 pub struct Event {
     #[serde(flatten)] // due to tagging options, this adds a "type" key and a "data" key
     pub data: EventType,
+    /// Block timestamp when the event was emitted, in nanoseconds. Serialized
+    /// as a string, since it doesn't fit in a JS number. Only present with the
+    /// `timestamped_events` feature.
+    #[cfg(feature = "timestamped_events")]
+    pub emitted_at_ns: U64,
+}
+
+impl Event {
+    /// Wrap event data for emission, stamping it with the current block
+    /// timestamp if the `timestamped_events` feature is enabled.
+    pub fn new(data: EventType) -> Self {
+        Self {
+            data,
+            #[cfg(feature = "timestamped_events")]
+            emitted_at_ns: U64(env::block_timestamp()),
+        }
+    }
 }
 
 impl fmt::Display for Event {
@@ -29,6 +48,32 @@ pub enum EventType {
     Fill(NewFillEvent),
     Cancel(NewCancelEvent),
     NewMarket(NewMarketEvent),
+    Reduce(NewReduceEvent),
+    Modify(NewModifyEvent),
+}
+
+/// Broad grouping of events, eg, for routing in an indexer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventCategory {
+    /// Fills.
+    Trade,
+    /// Orders being placed or cancelled.
+    Lifecycle,
+    /// Market creation.
+    Market,
+}
+
+impl EventType {
+    pub fn category(&self) -> EventCategory {
+        match self {
+            EventType::Fill(_) => EventCategory::Trade,
+            EventType::Order(_)
+            | EventType::Cancel(_)
+            | EventType::Reduce(_)
+            | EventType::Modify(_) => EventCategory::Lifecycle,
+            EventType::NewMarket(_) => EventCategory::Market,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -66,6 +111,32 @@ pub struct NewOrderEvent {
     pub client_id: Option<u32>,
 }
 
+impl NewOrderEvent {
+    /// Short description of the order, eg, `buy 5 @ 100 (limit)`. Quantity
+    /// and price are in native units. Market orders omit the price.
+    pub fn summary(&self) -> String {
+        let side = match self.side {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        };
+        let order_type = match self.order_type {
+            OrderType::Limit => "limit",
+            OrderType::ImmediateOrCancel => "immediate or cancel",
+            OrderType::PostOnly => "post only",
+            OrderType::FillOrKill => "fill or kill",
+            OrderType::Market => "market",
+        };
+        if self.order_type == OrderType::Market {
+            format!("{} {} ({})", side, self.quantity.0, order_type)
+        } else {
+            format!(
+                "{} {} @ {} ({})",
+                side, self.quantity.0, self.limit_price.0, order_type
+            )
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(crate = "near_sdk::serde", rename = "cancel_order")]
 pub struct NewCancelEvent {
@@ -74,9 +145,12 @@ pub struct NewCancelEvent {
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(crate = "near_sdk::serde", rename = "cancel_order")]
+#[serde(crate = "near_sdk::serde")]
 pub struct CancelEventData {
     pub order_id: OrderId,
+    /// Limit price of the cancelled order, denominated in the quote currency.
+    #[serde(default = "zero_u128")] // backwards compatibility
+    pub limit_price: U128,
     /// Amount of locked token refunded.
     pub refund_amount: U128,
     /// The token that was locked in the open order. Quote if bid, base if ask.
@@ -98,6 +172,37 @@ pub struct CancelEventData {
     pub best_ask: Option<U128>,
 }
 
+/// An open order's quantity was reduced without cancelling it.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde", rename = "reduce_order")]
+pub struct NewReduceEvent {
+    pub market_id: MarketId,
+    pub order_id: OrderId,
+    /// Quantity removed from the order.
+    pub reduced_by: U128,
+    /// Quantity still open after the reduction.
+    pub remaining_qty: U128,
+    /// Amount of locked token refunded. Quote if bid, base if ask.
+    pub refund_amount: U128,
+}
+
+/// An open order was replaced by a new order in one step, eg, to change its
+/// price or quantity. Consumers should treat this as the old order being
+/// removed and the new one posted, without a separate cancel or order event.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde", rename = "modify_order")]
+pub struct NewModifyEvent {
+    pub market_id: MarketId,
+    pub old_order_id: OrderId,
+    pub new_order_id: OrderId,
+    /// Open quantity of the old order when it was replaced.
+    pub old_qty: U128,
+    /// Open quantity of the new order.
+    pub new_qty: U128,
+    /// Limit price of the new order, denominated in the quote currency.
+    pub new_price: U128,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(crate = "near_sdk::serde", rename = "new_market")]
 pub struct NewMarketEvent {
@@ -107,6 +212,25 @@ pub struct NewMarketEvent {
     pub quote_token: TokenType,
 }
 
+impl NewMarketEvent {
+    /// Derive the market ID with [MarketId::from_parts] and build the event
+    /// announcing the market, so the emitted ID always matches the derivation.
+    pub fn create(
+        creator_id: AccountId,
+        base_token: TokenType,
+        quote_token: TokenType,
+    ) -> (MarketId, NewMarketEvent) {
+        let market_id = MarketId::from_parts(&creator_id, &base_token, &quote_token);
+        let event = NewMarketEvent {
+            creator_id,
+            market_id,
+            base_token,
+            quote_token,
+        };
+        (market_id, event)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(crate = "near_sdk::serde", rename = "new_fill")]
 pub struct NewFillEvent {
@@ -134,7 +258,365 @@ pub struct FillEventData {
     pub maker_price_rank: u32, // TODO: make this Option. new indexer can't index old events if this is required
 }
 
+fn zero_u128() -> U128 {
+    U128(0)
+}
+
 pub fn emit_event(data: EventType) {
+    if cfg!(feature = "no_emit") {
+        return;
+    }
+    env::log_str(&Event::new(data).to_string());
+}
+
+/// Emit several events in a single log line, serialized as a JSON array of
+/// [Event]. Cheaper than calling [emit_event] once per event, eg, for an order
+/// event followed by its fills.
+pub fn emit_events(events: Vec<EventType>) {
+    if cfg!(feature = "no_emit") || events.is_empty() {
+        return;
+    }
+    let events: Vec<Event> = events.into_iter().map(Event::new).collect();
+    env::log_str(
+        &serde_json::to_string(&events)
+            .unwrap_or_else(|_| env::panic_str("failed to serialize events")),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(deprecated)]
+    fn cancel_event_limit_price_round_trip() {
+        let quote_lot_size = 1_000;
+        let price_lots = 480;
+        let order_id = new_order_id(Side::Buy, price_lots, 1);
+        let (_, order_price_lots, _) = order_id.into_parts();
+
+        let data = CancelEventData {
+            order_id,
+            limit_price: U128(order_price_lots as u128 * quote_lot_size),
+            refund_amount: U128(480_000),
+            refund_token: TokenType::NativeNear,
+            cancelled_qty: U128(1),
+            price_rank: 0,
+            best_bid: None,
+            best_ask: None,
+        };
+        let json = serde_json::to_string(&data).unwrap();
+        let parsed: CancelEventData = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.order_id, order_id);
+        assert_eq!(parsed.limit_price, U128(480_000), "wrong native price");
+    }
+
     #[cfg(not(feature = "no_emit"))]
-    env::log_str(&Event { data }.to_string());
+    #[test]
+    #[allow(deprecated)]
+    fn emit_events_single_line() {
+        let market_id = MarketId([1; 32]);
+        let account_id = AccountId::new_unchecked("a.near".to_string());
+        let order_id = new_order_id(Side::Buy, 10, 1);
+
+        emit_events(vec![
+            EventType::NewMarket(NewMarketEvent {
+                creator_id: account_id.clone(),
+                market_id,
+                base_token: TokenType::NativeNear,
+                quote_token: TokenType::from_account_id(account_id),
+            }),
+            EventType::Fill(NewFillEvent {
+                market_id,
+                order_id,
+                fills: vec![],
+            }),
+        ]);
+
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs.len(), 1, "expected a single log line");
+
+        let parsed: Vec<Event> = serde_json::from_str(&logs[0]).unwrap();
+        assert_eq!(parsed.len(), 2);
+        match &parsed[0].data {
+            EventType::NewMarket(ev) => {
+                assert_eq!(ev.market_id, market_id);
+                assert_eq!(ev.base_token, TokenType::NativeNear);
+            }
+            _ => panic!("expected new market event"),
+        }
+        match &parsed[1].data {
+            EventType::Fill(ev) => {
+                assert_eq!(ev.order_id, order_id);
+                assert!(ev.fills.is_empty());
+            }
+            _ => panic!("expected fill event"),
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn event_categories() {
+        let market_id = MarketId([1; 32]);
+        let account_id = AccountId::new_unchecked("a.near".to_string());
+        let order_id = new_order_id(Side::Buy, 10, 1);
+
+        let order = EventType::Order(NewOrderEvent {
+            account_id: account_id.clone(),
+            order_id,
+            open_quantity: None,
+            market_id,
+            limit_price: U128(10),
+            price_rank: None,
+            best_bid: None,
+            best_ask: None,
+            quantity: U128(1),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            taker_fee: U128(0),
+            referrer_id: None,
+            referrer_rebate: U128(0),
+            is_swap: false,
+            client_id: None,
+        });
+        let fill = EventType::Fill(NewFillEvent {
+            market_id,
+            order_id,
+            fills: vec![],
+        });
+        let cancel = EventType::Cancel(NewCancelEvent {
+            market_id,
+            cancels: vec![],
+        });
+        let new_market = EventType::NewMarket(NewMarketEvent {
+            creator_id: account_id,
+            market_id,
+            base_token: TokenType::NativeNear,
+            quote_token: TokenType::NativeNear,
+        });
+
+        assert_eq!(order.category(), EventCategory::Lifecycle);
+        assert_eq!(fill.category(), EventCategory::Trade);
+        assert_eq!(cancel.category(), EventCategory::Lifecycle);
+        assert_eq!(new_market.category(), EventCategory::Market);
+    }
+
+    #[test]
+    fn emit_events_empty() {
+        emit_events(vec![]);
+        assert!(near_sdk::test_utils::get_logs().is_empty());
+    }
+
+    #[test]
+    fn cancel_event_limit_price_defaults() {
+        let json = r#"{
+            "order_id": "1111111111111111",
+            "refund_amount": "1",
+            "refund_token": { "type": "near" },
+            "cancelled_qty": "1",
+            "price_rank": 0,
+            "best_bid": null,
+            "best_ask": null
+        }"#;
+        let parsed: CancelEventData = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.limit_price, U128(0));
+    }
+
+    #[allow(deprecated)]
+    fn order_event(
+        side: Side,
+        order_type: OrderType,
+        quantity: u128,
+        price: u128,
+    ) -> NewOrderEvent {
+        NewOrderEvent {
+            account_id: AccountId::new_unchecked("a.near".to_string()),
+            order_id: new_order_id(side, 10, 1),
+            open_quantity: None,
+            market_id: MarketId([1; 32]),
+            limit_price: U128(price),
+            price_rank: None,
+            best_bid: None,
+            best_ask: None,
+            quantity: U128(quantity),
+            side,
+            order_type,
+            taker_fee: U128(0),
+            referrer_id: None,
+            referrer_rebate: U128(0),
+            is_swap: false,
+            client_id: None,
+        }
+    }
+
+    #[test]
+    fn order_event_summary() {
+        assert_eq!(
+            order_event(Side::Buy, OrderType::Limit, 5, 100).summary(),
+            "buy 5 @ 100 (limit)"
+        );
+        assert_eq!(
+            order_event(Side::Sell, OrderType::PostOnly, 20, 1_500).summary(),
+            "sell 20 @ 1500 (post only)"
+        );
+        assert_eq!(
+            order_event(Side::Buy, OrderType::Market, 7, 0).summary(),
+            "buy 7 (market)"
+        );
+    }
+
+    #[test]
+    fn reduce_event_round_trip() {
+        let market_id = MarketId([1; 32]);
+        let order_id = new_order_id(Side::Sell, 10, 1);
+        let event = Event::new(EventType::Reduce(NewReduceEvent {
+            market_id,
+            order_id,
+            reduced_by: U128(3),
+            remaining_qty: U128(2),
+            refund_amount: U128(30),
+        }));
+        let json = event.to_string();
+        assert!(json.starts_with(r#"{"type":"Reduce","data":{"#), "{}", json);
+
+        let parsed: Event = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.data.category(), EventCategory::Lifecycle);
+        match parsed.data {
+            EventType::Reduce(ev) => {
+                assert_eq!(ev.market_id, market_id);
+                assert_eq!(ev.order_id, order_id);
+                assert_eq!(ev.reduced_by, U128(3));
+                assert_eq!(ev.remaining_qty, U128(2));
+                assert_eq!(ev.refund_amount, U128(30));
+            }
+            _ => panic!("expected reduce event"),
+        }
+    }
+
+    /// The `type` tag is the discriminator consumers parse events by, so each
+    /// variant's tag must be unique and must not change.
+    #[test]
+    fn event_type_tags() {
+        let market_id = MarketId([1; 32]);
+        let order_id = new_order_id(Side::Sell, 10, 1);
+        let (_, new_market) = NewMarketEvent::create(
+            "creator.near".parse().unwrap(),
+            TokenType::NativeNear,
+            TokenType::from_key("ft:usdc.near"),
+        );
+        let events = vec![
+            EventType::Order(order_event(Side::Buy, OrderType::Limit, 5, 100)),
+            EventType::Fill(NewFillEvent {
+                market_id,
+                order_id,
+                fills: vec![],
+            }),
+            EventType::Cancel(NewCancelEvent {
+                market_id,
+                cancels: vec![],
+            }),
+            EventType::NewMarket(new_market),
+            EventType::Reduce(NewReduceEvent {
+                market_id,
+                order_id,
+                reduced_by: U128(3),
+                remaining_qty: U128(2),
+                refund_amount: U128(30),
+            }),
+            EventType::Modify(NewModifyEvent {
+                market_id,
+                old_order_id: order_id,
+                new_order_id: new_order_id(Side::Sell, 11, 2),
+                old_qty: U128(5),
+                new_qty: U128(4),
+                new_price: U128(11),
+            }),
+        ];
+
+        let tags: Vec<String> = events
+            .into_iter()
+            .map(|e| {
+                let json: serde_json::Value =
+                    serde_json::from_str(&Event::new(e).to_string()).unwrap();
+                json["type"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(
+            tags,
+            ["Order", "Fill", "Cancel", "NewMarket", "Reduce", "Modify"]
+        );
+        let unique: std::collections::HashSet<_> = tags.iter().collect();
+        assert_eq!(unique.len(), tags.len());
+    }
+
+    #[test]
+    fn modify_event_round_trip() {
+        let market_id = MarketId([1; 32]);
+        let old_order_id = new_order_id(Side::Buy, 10, 1);
+        let new_order_id = new_order_id(Side::Buy, 12, 2);
+        let event = Event::new(EventType::Modify(NewModifyEvent {
+            market_id,
+            old_order_id,
+            new_order_id,
+            old_qty: U128(5),
+            new_qty: U128(8),
+            new_price: U128(120),
+        }));
+        let json = event.to_string();
+        assert!(json.starts_with(r#"{"type":"Modify","data":{"#), "{}", json);
+
+        let parsed: Event = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.data.category(), EventCategory::Lifecycle);
+        assert_eq!(parsed.to_string(), json);
+        match parsed.data {
+            EventType::Modify(ev) => {
+                assert_eq!(ev.market_id, market_id);
+                assert_eq!(ev.old_order_id, old_order_id);
+                assert_eq!(ev.new_order_id, new_order_id);
+                assert_eq!(ev.old_qty, U128(5));
+                assert_eq!(ev.new_qty, U128(8));
+                assert_eq!(ev.new_price, U128(120));
+            }
+            _ => panic!("expected modify event"),
+        }
+    }
+
+    #[test]
+    fn new_market_event_create() {
+        let creator_id: AccountId = "creator.near".parse().unwrap();
+        let base = TokenType::NativeNear;
+        let quote = TokenType::from_key("ft:usdc.near");
+
+        let (market_id, event) =
+            NewMarketEvent::create(creator_id.clone(), base.clone(), quote.clone());
+        assert_eq!(market_id, MarketId::from_parts(&creator_id, &base, &quote));
+        assert_eq!(event.market_id, market_id);
+        assert_eq!(event.creator_id, creator_id);
+        assert_eq!(event.base_token, base);
+        assert_eq!(event.quote_token, quote);
+    }
+
+    #[cfg(all(feature = "timestamped_events", not(feature = "no_emit")))]
+    #[test]
+    fn timestamped_event_round_trip() {
+        let mut context = near_sdk::test_utils::VMContextBuilder::new();
+        context.block_timestamp(1_650_000_000_000_000_000);
+        near_sdk::testing_env!(context.build());
+
+        emit_event(EventType::Cancel(NewCancelEvent {
+            market_id: MarketId([1; 32]),
+            cancels: vec![],
+        }));
+        let logs = near_sdk::test_utils::get_logs();
+        assert!(
+            logs[0].contains(r#""emitted_at_ns":"1650000000000000000""#),
+            "{}",
+            logs[0]
+        );
+
+        let parsed: Event = serde_json::from_str(&logs[0]).unwrap();
+        assert_eq!(parsed.emitted_at_ns, U64(1_650_000_000_000_000_000));
+        assert_eq!(parsed.to_string(), logs[0]);
+    }
 }
@@ -0,0 +1,88 @@
+//! Helpers for building orderbooks in benchmarks.
+use near_sdk::AccountId;
+use tonic_sdk_dex_orderbook::*;
+use tonic_sdk_dex_types::*;
+
+pub const MAKER: &str = "maker.near";
+pub const TAKER: &str = "taker.near";
+
+/// Builds a [VecOrderbook] with a configurable number of resting orders.
+///
+/// Asks rest at `mid + 1, mid + 2, ...` and bids at `mid - 1, mid - 2, ...`,
+/// so every resting order has a unique price level. Lot sizes and
+/// denomination are all 1 to keep the math out of the way.
+pub struct BookBuilder {
+    ob: VecOrderbook,
+    sequence_number: SequenceNumber,
+    mid_price_lots: LotBalance,
+}
+
+impl BookBuilder {
+    pub fn new(mid_price_lots: LotBalance) -> Self {
+        Self {
+            ob: VecOrderbook::default(),
+            sequence_number: 0,
+            mid_price_lots,
+        }
+    }
+
+    /// Add `count` resting orders of `qty_lots` each on `side`.
+    pub fn resting(mut self, side: Side, count: u64, qty_lots: LotBalance) -> Self {
+        let maker = AccountId::new_unchecked(MAKER.to_string());
+        for i in 1..=count {
+            let price = match side {
+                Side::Buy => self.mid_price_lots - i,
+                Side::Sell => self.mid_price_lots + i,
+            };
+            let order = self.new_order(side, OrderType::Limit, Some(price), qty_lots);
+            self.ob.place_order(&maker, order);
+        }
+        self
+    }
+
+    /// Add `count` orders to each side.
+    pub fn resting_both(self, count: u64, qty_lots: LotBalance) -> Self {
+        self.resting(Side::Buy, count, qty_lots)
+            .resting(Side::Sell, count, qty_lots)
+    }
+
+    /// Create a new order request with the next sequence number.
+    pub fn new_order(
+        &mut self,
+        side: Side,
+        order_type: OrderType,
+        limit_price_lots: Option<LotBalance>,
+        max_qty_lots: LotBalance,
+    ) -> NewOrder {
+        self.sequence_number += 1;
+        NewOrder {
+            sequence_number: self.sequence_number,
+            limit_price_lots,
+            available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
+            max_qty_lots,
+            side,
+            order_type,
+            base_denomination: 1,
+            quote_lot_size: 1,
+            base_lot_size: 1,
+            client_id: None,
+        }
+    }
+
+    pub fn orderbook(&self) -> &VecOrderbook {
+        &self.ob
+    }
+
+    /// Copy of the current book, eg, for use as a benchmark input.
+    pub fn snapshot(&self) -> VecOrderbook {
+        Orderbook::new(self.ob.bids.clone(), self.ob.asks.clone())
+    }
+
+    pub fn mid_price_lots(&self) -> LotBalance {
+        self.mid_price_lots
+    }
+}
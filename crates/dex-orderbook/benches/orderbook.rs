@@ -0,0 +1,101 @@
+//! Matching engine benchmarks.
+//!
+//! Run with `cargo bench -p tonic-sdk-dex-orderbook`. To check that the
+//! benchmarks build and run without measuring anything (eg, in CI), run
+//! `cargo test -p tonic-sdk-dex-orderbook --benches`, which runs each
+//! benchmark once.
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use near_sdk::AccountId;
+use tonic_sdk_dex_orderbook::*;
+use tonic_sdk_dex_types::*;
+
+mod common;
+use common::*;
+
+const DEPTHS: [u64; 3] = [100, 1_000, 10_000];
+
+/// Number of price levels swept by the match-heavy benchmark.
+const SWEEP_LEVELS: u64 = 50;
+
+const MID_PRICE_LOTS: LotBalance = 1_000_000;
+
+fn place_order(c: &mut Criterion) {
+    let mut group = c.benchmark_group("place_order");
+    let taker = AccountId::new_unchecked(TAKER.to_string());
+
+    for depth in DEPTHS {
+        let mut builder = BookBuilder::new(MID_PRICE_LOTS).resting_both(depth, 1);
+
+        // post at the back of the book, which is the worst case for the
+        // price-rank lookup
+        let worst_bid = builder.mid_price_lots() - depth - 1;
+        let order = builder.new_order(Side::Buy, OrderType::Limit, Some(worst_bid), 1);
+        group.bench_with_input(BenchmarkId::new("post", depth), &depth, |b, _| {
+            b.iter_batched(
+                || (builder.snapshot(), order.clone()),
+                |(mut ob, order)| ob.place_order(&taker, order),
+                BatchSize::LargeInput,
+            )
+        });
+
+        // cross the spread and fill against the best ask
+        let best_ask = builder.mid_price_lots() + 1;
+        let order = builder.new_order(Side::Buy, OrderType::Limit, Some(best_ask), 1);
+        group.bench_with_input(BenchmarkId::new("take", depth), &depth, |b, _| {
+            b.iter_batched(
+                || (builder.snapshot(), order.clone()),
+                |(mut ob, order)| ob.place_order(&taker, order),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn cancel_order(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cancel_order");
+
+    for depth in DEPTHS {
+        let builder = BookBuilder::new(MID_PRICE_LOTS).resting_both(depth, 1);
+        let middle_bid = builder.mid_price_lots() - depth / 2;
+        let order_id = builder
+            .orderbook()
+            .bids
+            .iter()
+            .find(|o| o.unwrap_price() == middle_bid)
+            .map(|o| o.id())
+            .unwrap();
+
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &depth, |b, _| {
+            b.iter_batched(
+                || builder.snapshot(),
+                |mut ob| ob.cancel_order(order_id),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn match_heavy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("match_heavy");
+    let taker = AccountId::new_unchecked(TAKER.to_string());
+
+    for depth in DEPTHS {
+        let mut builder = BookBuilder::new(MID_PRICE_LOTS).resting_both(depth, 1);
+
+        // sweep several levels of asks with a single market order
+        let order = builder.new_order(Side::Buy, OrderType::Market, None, SWEEP_LEVELS);
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &depth, |b, _| {
+            b.iter_batched(
+                || (builder.snapshot(), order.clone()),
+                |(mut ob, order)| ob.place_order(&taker, order),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, place_order, cancel_order, match_heavy);
+criterion_main!(benches);
@@ -0,0 +1,85 @@
+/// Conversion of matching results to emitted events.
+use near_sdk::json_types::U128;
+use near_sdk::AccountId;
+use tonic_sdk_dex_events::{EventType, FillEventData, NewFillEvent, NewOrderEvent};
+use tonic_sdk_dex_types::MarketId;
+
+use crate::*;
+
+impl PlaceOrderResult {
+    /// Build the order event, followed by a fill event if the order matched.
+    /// Quantities and prices are converted to native units with `calc`.
+    ///
+    /// The orderbook doesn't know about fees or referrals, so those fields are
+    /// zero. `best_bid` and `best_ask` are taken from the result.
+    #[allow(deprecated)]
+    pub fn to_events(
+        &self,
+        user_id: &AccountId,
+        order: &NewOrder,
+        market_id: MarketId,
+        calc: &OrderbookCalculator,
+    ) -> Vec<EventType> {
+        let base_native = |lots| U128(conv::lots_to_balance(lots) * calc.base_lot_size);
+        let quote_native = |lots| U128(conv::lots_to_balance(lots) * calc.quote_lot_size);
+
+        let mut events = vec![EventType::Order(NewOrderEvent {
+            account_id: user_id.clone(),
+            order_id: self.id,
+            open_quantity: Some(base_native(self.open_qty_lots)),
+            market_id,
+            limit_price: quote_native(order.limit_price_lots.unwrap_or_default()),
+            price_rank: self.price_rank,
+            best_bid: self.best_bid.map(quote_native),
+            best_ask: self.best_ask.map(quote_native),
+            quantity: base_native(order.max_qty_lots),
+            side: order.side,
+            order_type: order.order_type,
+            taker_fee: U128(0),
+            referrer_id: None,
+            referrer_rebate: U128(0),
+            is_swap: false,
+            client_id: order.client_id,
+        })];
+        if !self.matches.is_empty() {
+            events.push(EventType::Fill(NewFillEvent {
+                market_id,
+                order_id: self.id,
+                fills: self
+                    .matches
+                    .iter()
+                    .map(|m| FillEventData {
+                        maker_order_id: m.maker_order_id,
+                        fill_qty: U128(m.native_base_filled),
+                        fill_price: quote_native(m.fill_price_lots),
+                        quote_qty: U128(m.native_quote_paid),
+                        maker_rebate: U128(0),
+                        side: order.side,
+                        taker_account_id: user_id.clone(),
+                        maker_account_id: m.maker_user_id.clone(),
+                        maker_price_rank: m.maker_order_price_rank,
+                    })
+                    .collect(),
+            }));
+        }
+        events
+    }
+}
+
+impl<T: L2> Orderbook<T> {
+    /// Same as [place_order](Orderbook::place_order), also returning the
+    /// events describing the result, ready for
+    /// [emit_events](tonic_sdk_dex_events::emit_events). See
+    /// [to_events](PlaceOrderResult::to_events).
+    pub fn place_order_and_events(
+        &mut self,
+        user_id: &AccountId,
+        order: NewOrder,
+        market_id: MarketId,
+        calc: &OrderbookCalculator,
+    ) -> (PlaceOrderResult, Vec<EventType>) {
+        let res = self.place_order(user_id, order.clone());
+        let events = res.to_events(user_id, &order, market_id, calc);
+        (res, events)
+    }
+}
@@ -0,0 +1,11 @@
+use near_sdk::AccountId;
+use tonic_sdk_dex_types::LotBalance;
+
+/// Borrowed view of the order at one end of an [crate::L2]. Cheaper than a
+/// full [crate::OpenLimitOrder] when only peeking at the top of the book.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BboView<'a> {
+    pub price_lots: LotBalance,
+    pub open_qty_lots: LotBalance,
+    pub owner_id: &'a AccountId,
+}
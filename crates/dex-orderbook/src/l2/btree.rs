@@ -0,0 +1,265 @@
+/// Orderbook backend implemented as an ordered map of orders. Inserts and
+/// deletes are logarithmic instead of shifting a [VecL2](super::vec::VecL2),
+/// at the cost of a larger serialized size. Orders iterate in the same order
+/// as the vec backend, so the two are interchangeable.
+use std::collections::BTreeMap;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use tonic_sdk_dex_types::{new_order_id, LotBalance, OrderId, SequenceNumber, Side};
+
+use crate::l2::vec::VecL2;
+use crate::*;
+
+/// `(sort key, sequence number)`. The sort key is the price, bitwise negated
+/// on the bid side so that iteration is always best price first.
+type OrderKey = (LotBalance, SequenceNumber);
+
+/// One side of an orderbook, keyed by price and sequence number.
+#[derive(Debug, Default, Clone, BorshDeserialize, BorshSerialize)]
+pub struct BTreeL2 {
+    orders: BTreeMap<OrderKey, OpenLimitOrder>,
+
+    /// Whether prices should be sorted in reverse (ie descending order). When
+    /// true (eg for the bid side), the best price is the highest.
+    pub reverse_prices: bool,
+}
+
+/// Compares prices and the stored order fields only. See [VecL2].
+impl PartialEq for BTreeL2 {
+    fn eq(&self, other: &Self) -> bool {
+        self.reverse_prices == other.reverse_prices
+            && self.orders.len() == other.orders.len()
+            && self
+                .orders
+                .iter()
+                .zip(other.orders.iter())
+                .all(|((k1, o1), (k2, o2))| {
+                    k1 == k2
+                        && o1.owner_id == o2.owner_id
+                        && o1.open_qty_lots == o2.open_qty_lots
+                        && o1.client_id == o2.client_id
+                })
+    }
+}
+
+impl Eq for BTreeL2 {}
+
+impl OrderIter for BTreeL2 {
+    /// Iterate through all orders, best price first
+    fn iter(&self) -> Box<dyn Iterator<Item = OpenLimitOrder> + '_> {
+        let mut rank = 0;
+        let mut prev_key: Option<LotBalance> = None;
+        Box::new(self.orders.iter().map(move |(key, order)| {
+            if prev_key.is_some() && prev_key != Some(key.0) {
+                rank += 1;
+            }
+            prev_key = Some(key.0);
+            self.initialized(*key, order.clone(), rank)
+        }))
+    }
+}
+
+impl L2 for BTreeL2 {
+    fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    fn max_order(&self) -> Option<OpenLimitOrder> {
+        let (key, order) = self.max_entry()?;
+        Some(self.initialized(*key, order.clone(), self.rank_of_key(key.0)))
+    }
+
+    fn min_order(&self) -> Option<OpenLimitOrder> {
+        let (key, order) = self.min_entry()?;
+        Some(self.initialized(*key, order.clone(), self.rank_of_key(key.0)))
+    }
+
+    fn peek_max(&self) -> Option<BboView<'_>> {
+        self.max_entry()
+            .map(|(key, order)| self.bbo_view(key, order))
+    }
+
+    fn peek_min(&self) -> Option<BboView<'_>> {
+        self.min_entry()
+            .map(|(key, order)| self.bbo_view(key, order))
+    }
+
+    fn save_order(&mut self, order: OpenLimitOrder) {
+        let key = self.key(order.unwrap_price(), order.sequence_number);
+        // a different owner means two orders share an ID
+        #[cfg(any(test, feature = "integrity_checks"))]
+        if let Some(existing) = self.orders.get(&key) {
+            tonic_sdk_macros::_assert!(existing.owner_id == order.owner_id, "order ID collision");
+        }
+        self.orders.insert(key, order.into_stored());
+    }
+
+    fn get_order(&self, price_lots: LotBalance, seq: SequenceNumber) -> Option<OpenLimitOrder> {
+        let key = self.key(price_lots, seq);
+        self.orders
+            .get(&key)
+            .map(|order| self.initialized(key, order.clone(), self.rank_of_key(key.0)))
+    }
+
+    fn delete_order(
+        &mut self,
+        price_lots: LotBalance,
+        seq: SequenceNumber,
+    ) -> Option<OpenLimitOrder> {
+        let key = self.key(price_lots, seq);
+        let price_rank = self.rank_of_key(key.0);
+        let order = self.orders.remove(&key)?;
+        Some(self.initialized(key, order, price_rank))
+    }
+
+    fn get_price_rank(&self, price_lots: LotBalance) -> u32 {
+        self.rank_of_key(self.key(price_lots, 0).0)
+    }
+
+    fn has_price_level(&self, price_lots: LotBalance) -> bool {
+        let key = self.key(price_lots, 0).0;
+        self.orders
+            .range((key, 0)..=(key, SequenceNumber::MAX))
+            .next()
+            .is_some()
+    }
+
+    fn unique_prices_count(&self) -> u32 {
+        count_distinct(self.orders.keys().map(|k| k.0))
+    }
+
+    fn price_ranks(&self) -> Vec<(OrderId, u32)> {
+        let side = self.side();
+        self.iter()
+            .map(|o| {
+                (
+                    new_order_id(side, o.unwrap_price(), o.sequence_number),
+                    o.unwrap_price_rank(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl BTreeL2 {
+    pub fn new(reverse_prices: bool) -> Self {
+        Self {
+            reverse_prices,
+            ..Default::default()
+        }
+    }
+
+    fn side(&self) -> Side {
+        if self.reverse_prices {
+            Side::Buy
+        } else {
+            Side::Sell
+        }
+    }
+
+    fn key(&self, price_lots: LotBalance, seq: SequenceNumber) -> OrderKey {
+        if self.reverse_prices {
+            (!price_lots, seq)
+        } else {
+            (price_lots, seq)
+        }
+    }
+
+    fn price(&self, key: OrderKey) -> LotBalance {
+        if self.reverse_prices {
+            !key.0
+        } else {
+            key.0
+        }
+    }
+
+    /// Entry with the greatest `(price, sequence number)`, ie, the newest
+    /// order at the highest price. Same as [VecL2].
+    fn max_entry(&self) -> Option<(&OrderKey, &OpenLimitOrder)> {
+        if self.reverse_prices {
+            let best = self.orders.keys().next()?.0;
+            self.orders
+                .range((best, 0)..=(best, SequenceNumber::MAX))
+                .next_back()
+        } else {
+            self.orders.iter().next_back()
+        }
+    }
+
+    /// Entry with the least `(price, sequence number)`, ie, the oldest order
+    /// at the lowest price.
+    fn min_entry(&self) -> Option<(&OrderKey, &OpenLimitOrder)> {
+        if self.reverse_prices {
+            let worst = self.orders.keys().next_back()?.0;
+            self.orders.range((worst, 0)..).next()
+        } else {
+            self.orders.iter().next()
+        }
+    }
+
+    /// Number of distinct price levels ahead of the sort key.
+    fn rank_of_key(&self, sort_key: LotBalance) -> u32 {
+        count_distinct(self.orders.range(..(sort_key, 0)).map(|(k, _)| k.0))
+    }
+
+    fn initialized(
+        &self,
+        key: OrderKey,
+        mut order: OpenLimitOrder,
+        price_rank: u32,
+    ) -> OpenLimitOrder {
+        order.initialize_price(self.price(key));
+        order.initialize_side(self.side());
+        order.initialize_price_rank(price_rank);
+        order
+    }
+
+    fn bbo_view<'a>(&self, key: &OrderKey, order: &'a OpenLimitOrder) -> BboView<'a> {
+        BboView {
+            price_lots: self.price(*key),
+            open_qty_lots: order.open_qty_lots,
+            owner_id: &order.owner_id,
+        }
+    }
+}
+
+/// Count distinct values in a sorted iterator.
+fn count_distinct(sorted: impl Iterator<Item = LotBalance>) -> u32 {
+    let mut count = 0;
+    let mut prev = None;
+    for value in sorted {
+        if prev != Some(value) {
+            count += 1;
+            prev = Some(value);
+        }
+    }
+    count
+}
+
+impl From<VecL2> for BTreeL2 {
+    fn from(l2: VecL2) -> Self {
+        let mut ret = BTreeL2::new(l2.reverse_prices);
+        ret.orders = l2
+            .orders
+            .into_iter()
+            .map(|(price, order)| (ret.key(price, order.sequence_number), order))
+            .collect();
+        ret
+    }
+}
+
+impl From<BTreeL2> for VecL2 {
+    fn from(l2: BTreeL2) -> Self {
+        // both backends sort by the same key, so the map is already in vec
+        // order
+        let orders = l2
+            .orders
+            .iter()
+            .map(|(key, order)| (l2.price(*key), order.clone()))
+            .collect();
+        VecL2 {
+            orders,
+            reverse_prices: l2.reverse_prices,
+        }
+    }
+}
@@ -1,7 +1,10 @@
+pub mod bbo;
+pub mod btree;
 pub mod traits;
 pub mod tvl;
 pub mod vec;
 
+pub use bbo::*;
 pub use traits::*;
 pub use tvl::*;
 
@@ -56,6 +56,17 @@ impl OpenLimitOrder {
 }
 
 impl OpenLimitOrder {
+    /// Clear the lazily initialized fields, leaving the order as it would be
+    /// after a Borsh round trip. [L2] implementations store orders this way, so
+    /// reads initialize the fields from scratch instead of replacing values
+    /// (and, with `debug_log`, logging that they were already initialized).
+    pub fn into_stored(mut self) -> Self {
+        self.limit_price_lots = None;
+        self.side = None;
+        self.price_rank = None;
+        self
+    }
+
     pub fn id(&self) -> OrderId {
         new_order_id(
             self.unwrap_side(),
@@ -63,6 +74,30 @@ impl OpenLimitOrder {
             self.sequence_number,
         )
     }
+
+    /// Native value locked in the order: quote for a bid, base for an ask.
+    pub fn remaining_native_value(&self, calc: &OrderbookCalculator) -> Balance {
+        let tvl = self.value_locked(
+            calc.base_lot_size,
+            calc.quote_lot_size,
+            calc.base_denomination,
+        );
+        match self.unwrap_side() {
+            Side::Buy => tvl.quote_locked,
+            Side::Sell => tvl.base_locked,
+        }
+    }
+
+    /// Native amount to refund when the order is cancelled: quote for a bid,
+    /// base for an ask.
+    ///
+    /// An ask's refund is exact, since base is locked in whole lots. A bid's
+    /// refund is its open quantity's value at the limit price, rounded down,
+    /// so the refunds of all cancelled orders never exceed what the book
+    /// holds.
+    pub fn refund_on_cancel(&self, calc: &OrderbookCalculator) -> Balance {
+        self.remaining_native_value(calc)
+    }
 }
 
 impl ValueLocked for OpenLimitOrder {
@@ -2,7 +2,7 @@ use near_sdk::{
     borsh::{BorshDeserialize, BorshSerialize},
     Balance,
 };
-use tonic_sdk_dex_types::{LotBalance, SequenceNumber};
+use tonic_sdk_dex_types::{LotBalance, OrderId, SequenceNumber};
 
 use crate::*;
 
@@ -13,6 +13,12 @@ pub trait L2: BorshDeserialize + BorshSerialize + OrderIter + TakeL2Depth {
     /// The order with the least price.
     fn min_order(&self) -> Option<OpenLimitOrder>;
 
+    /// Same order as [max_order](L2::max_order), without cloning it.
+    fn peek_max(&self) -> Option<BboView<'_>>;
+
+    /// Same order as [min_order](L2::min_order), without cloning it.
+    fn peek_min(&self) -> Option<BboView<'_>>;
+
     /// Save an order.
     fn save_order(&mut self, order: OpenLimitOrder);
 
@@ -24,6 +30,29 @@ pub trait L2: BorshDeserialize + BorshSerialize + OrderIter + TakeL2Depth {
 
     fn get_price_rank(&self, price_lots: LotBalance) -> u32;
 
+    /// Return true if at least one order rests at the price.
+    fn has_price_level(&self, price_lots: LotBalance) -> bool {
+        self.iter().any(|o| o.unwrap_price() == price_lots)
+    }
+
+    /// Return number of unique price levels.
+    fn unique_prices_count(&self) -> u32 {
+        let mut count = 0;
+        let mut prev_price = None;
+        for price in self.iter().map(|o| o.unwrap_price()) {
+            if prev_price != Some(price) {
+                count += 1;
+                prev_price = Some(price);
+            }
+        }
+        count
+    }
+
+    /// IDs of all orders, best price first, with their price ranks. Unlike
+    /// calling [get_price_rank](L2::get_price_rank) per order, this computes
+    /// all ranks in one pass.
+    fn price_ranks(&self) -> Vec<(OrderId, u32)>;
+
     fn delete_order(
         &mut self,
         price_lots: LotBalance,
@@ -45,6 +74,33 @@ pub trait OrderIter {
 /// Used to make [crate::OrderbookView].
 pub trait TakeL2Depth {
     fn take_depth(&self, depth: usize) -> Vec<(LotBalance, Vec<OpenLimitOrder>)>;
+
+    /// Aggregate open quantity into fixed-width price buckets, eg, for a
+    /// zoomed-out depth chart. Returns `(bucket floor, total quantity)` for
+    /// the first `num_buckets` non-empty buckets, best price first. A bucket
+    /// with floor `f` holds prices in `f..f + bucket_size_lots`.
+    fn take_depth_bucketed(
+        &self,
+        bucket_size_lots: LotBalance,
+        num_buckets: usize,
+    ) -> Vec<(LotBalance, LotBalance)>;
+
+    /// Same as [take_depth](TakeL2Depth::take_depth), but with levels sorted
+    /// by price in the requested direction. The levels included are still the
+    /// `depth` best levels; only the output order changes.
+    fn take_depth_ordered(
+        &self,
+        depth: usize,
+        ascending: bool,
+    ) -> Vec<(LotBalance, Vec<OpenLimitOrder>)> {
+        let mut levels = self.take_depth(depth);
+        if ascending {
+            levels.sort_by_key(|(price, _)| *price);
+        } else {
+            levels.sort_by_key(|(price, _)| std::cmp::Reverse(*price));
+        }
+        levels
+    }
 }
 
 impl<T> TakeL2Depth for T
@@ -52,33 +108,79 @@ where
     T: OrderIter,
 {
     fn take_depth(&self, depth: usize) -> Vec<(LotBalance, Vec<OpenLimitOrder>)> {
-        let mut ret: Vec<(LotBalance, Vec<OpenLimitOrder>)> = vec![];
+        group_by_price(self.iter(), depth)
+    }
 
-        let mut curr_acc: Vec<OpenLimitOrder> = vec![];
-        let mut curr_price: Option<LotBalance> = None;
+    fn take_depth_bucketed(
+        &self,
+        bucket_size_lots: LotBalance,
+        num_buckets: usize,
+    ) -> Vec<(LotBalance, LotBalance)> {
+        bucket_by_price(self.iter(), bucket_size_lots, num_buckets)
+    }
+}
 
-        for order in self.iter() {
-            if ret.len() >= depth {
-                break;
-            }
-            if curr_price.is_none() {
-                curr_price = Some(order.unwrap_price());
-            }
-            if curr_price.unwrap() != order.unwrap_price() {
-                ret.push((curr_price.unwrap(), curr_acc.clone()));
-                curr_price = Some(order.unwrap_price());
-                curr_acc = vec![];
-            }
-            curr_acc.push(order);
-        }
+/// Group consecutive orders with the same price into levels, stopping after
+/// `depth` levels. Orders must already be sorted by price.
+pub(crate) fn group_by_price(
+    orders: impl Iterator<Item = OpenLimitOrder>,
+    depth: usize,
+) -> Vec<(LotBalance, Vec<OpenLimitOrder>)> {
+    let mut ret: Vec<(LotBalance, Vec<OpenLimitOrder>)> = vec![];
+
+    let mut curr_acc: Vec<OpenLimitOrder> = vec![];
+    let mut curr_price: Option<LotBalance> = None;
 
-        // base case: orderbook finished iterating but all orders had same price
-        if !curr_acc.is_empty() {
+    for order in orders {
+        if ret.len() >= depth {
+            break;
+        }
+        if curr_price.is_none() {
+            curr_price = Some(order.unwrap_price());
+        }
+        if curr_price.unwrap() != order.unwrap_price() {
             ret.push((curr_price.unwrap(), curr_acc.clone()));
+            curr_price = Some(order.unwrap_price());
+            curr_acc = vec![];
         }
+        curr_acc.push(order);
+    }
+
+    // base case: orderbook finished iterating but all orders had same price
+    // (skipped if the loop exited because the depth was already reached)
+    if !curr_acc.is_empty() && ret.len() < depth {
+        ret.push((curr_price.unwrap(), curr_acc.clone()));
+    }
+
+    ret
+}
 
-        ret
+/// Sum the quantity of orders into buckets of `bucket_size_lots` prices,
+/// stopping after `num_buckets` buckets. Orders must already be sorted by
+/// price.
+fn bucket_by_price(
+    orders: impl Iterator<Item = OpenLimitOrder>,
+    bucket_size_lots: LotBalance,
+    num_buckets: usize,
+) -> Vec<(LotBalance, LotBalance)> {
+    tonic_sdk_macros::_assert!(bucket_size_lots > 0, "bucket size is 0");
+    let mut ret: Vec<(LotBalance, LotBalance)> = vec![];
+
+    for order in orders {
+        let price = order.unwrap_price();
+        let floor = price - price % bucket_size_lots;
+        match ret.last_mut() {
+            Some((curr_floor, qty)) if *curr_floor == floor => *qty += order.open_qty_lots,
+            _ => {
+                if ret.len() >= num_buckets {
+                    break;
+                }
+                ret.push((floor, order.open_qty_lots));
+            }
+        }
     }
+
+    ret
 }
 
 /// Trait for structs that represent ownership of base and/or quote tokens.
@@ -30,3 +30,38 @@ impl Sum for Tvl {
         )
     }
 }
+
+impl<'a> Sum<&'a Tvl> for Tvl {
+    fn sum<I: Iterator<Item = &'a Tvl>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sum_by_reference() {
+        let tvls = [
+            Tvl {
+                base_locked: 1,
+                quote_locked: 10,
+            },
+            Tvl {
+                base_locked: 2,
+                quote_locked: 20,
+            },
+        ];
+        let total: Tvl = tvls.iter().sum();
+        assert_eq!(
+            total,
+            Tvl {
+                base_locked: 3,
+                quote_locked: 30
+            }
+        );
+        assert_eq!(total, tvls.iter().copied().sum());
+        assert_eq!([].iter().sum::<Tvl>(), Tvl::default());
+    }
+}
@@ -5,7 +5,7 @@
 /// have unique prices. Storing as a flat vec eliminates the storage overhead of
 /// vec serialization.
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use tonic_sdk_dex_types::{LotBalance, SequenceNumber, Side};
+use tonic_sdk_dex_types::{new_order_id, LotBalance, OrderId, SequenceNumber, Side};
 
 use crate::*;
 
@@ -36,6 +36,29 @@ impl VecL2 {
     }
 }
 
+/// Compares prices and the stored order fields only. The lazily initialized
+/// fields on [OpenLimitOrder] aren't stored and may differ depending on how an
+/// order was saved.
+impl PartialEq for VecL2 {
+    fn eq(&self, other: &Self) -> bool {
+        self.reverse_prices == other.reverse_prices
+            && self.orders.len() == other.orders.len()
+            && self
+                .orders
+                .iter()
+                .zip(other.orders.iter())
+                .all(|((p1, o1), (p2, o2))| {
+                    p1 == p2
+                        && o1.sequence_number == o2.sequence_number
+                        && o1.owner_id == o2.owner_id
+                        && o1.open_qty_lots == o2.open_qty_lots
+                        && o1.client_id == o2.client_id
+                })
+    }
+}
+
+impl Eq for VecL2 {}
+
 impl OrderIter for VecL2 {
     /// Iterate through all orders (flattens price levels)
     fn iter(&self) -> Box<dyn Iterator<Item = OpenLimitOrder> + '_> {
@@ -74,11 +97,44 @@ impl L2 for VecL2 {
             })
     }
 
+    fn peek_max(&self) -> Option<BboView<'_>> {
+        let idx = if self.reverse_prices {
+            // highest price is first; take the newest order at that price
+            let best = self.orders.first()?.0;
+            self.orders.partition_point(|(p, _)| *p == best) - 1
+        } else {
+            self.orders.len().checked_sub(1)?
+        };
+        Some(self.bbo_view(idx))
+    }
+
+    fn peek_min(&self) -> Option<BboView<'_>> {
+        let idx = if self.reverse_prices {
+            // lowest price is last; take the oldest order at that price
+            let worst = self.orders.last()?.0;
+            self.orders.partition_point(|(p, _)| *p != worst)
+        } else if self.orders.is_empty() {
+            return None;
+        } else {
+            0
+        };
+        Some(self.bbo_view(idx))
+    }
+
     fn save_order(&mut self, order: OpenLimitOrder) {
         let price = order.unwrap_price();
         match self.find_order_loc(price, order.sequence_number) {
-            Ok(loc) => self.orders[loc] = (price, order),
-            Err(loc) => self.orders.insert(loc, (price, order)),
+            Ok(loc) => {
+                // only expected when updating an existing order, eg, after a
+                // partial fill. a different owner means two orders share an ID
+                #[cfg(any(test, feature = "integrity_checks"))]
+                tonic_sdk_macros::_assert!(
+                    self.orders[loc].1.owner_id == order.owner_id,
+                    "order ID collision"
+                );
+                self.orders[loc] = (price, order.into_stored())
+            }
+            Err(loc) => self.orders.insert(loc, (price, order.into_stored())),
         }
     }
 
@@ -118,18 +174,12 @@ impl L2 for VecL2 {
             Err(rank) => rank as u32,
         }
     }
-}
 
-impl VecL2 {
-    pub fn new(reverse_prices: bool) -> Self {
-        Self {
-            reverse_prices,
-            ..Default::default()
-        }
+    fn has_price_level(&self, price_lots: LotBalance) -> bool {
+        self.get_price_rank_result(price_lots).is_ok()
     }
 
-    /// Return number of unique price levels.
-    pub fn unique_prices_count(&self) -> u32 {
+    fn unique_prices_count(&self) -> u32 {
         if self.orders.is_empty() {
             return 0;
         }
@@ -144,6 +194,48 @@ impl VecL2 {
         count
     }
 
+    fn price_ranks(&self) -> Vec<(OrderId, u32)> {
+        let side = self.side();
+        let mut rank = 0;
+        let mut prev_price = self.orders.first().map(|(p, _)| *p);
+        self.orders
+            .iter()
+            .map(|(p, o)| {
+                if prev_price != Some(*p) {
+                    rank += 1;
+                    prev_price = Some(*p);
+                }
+                (new_order_id(side, *p, o.sequence_number), rank)
+            })
+            .collect()
+    }
+}
+
+impl VecL2 {
+    pub fn new(reverse_prices: bool) -> Self {
+        Self {
+            reverse_prices,
+            ..Default::default()
+        }
+    }
+
+    /// Create an empty side with room for `cap` orders preallocated.
+    pub fn with_capacity(reverse_prices: bool, cap: usize) -> Self {
+        Self {
+            orders: Vec::with_capacity(cap),
+            reverse_prices,
+        }
+    }
+
+    fn bbo_view(&self, idx: usize) -> BboView<'_> {
+        let (price_lots, order) = &self.orders[idx];
+        BboView {
+            price_lots: *price_lots,
+            open_qty_lots: order.open_qty_lots,
+            owner_id: &order.owner_id,
+        }
+    }
+
     fn side(&self) -> Side {
         if self.reverse_prices {
             Side::Buy
@@ -153,17 +245,57 @@ impl VecL2 {
     }
 
     fn find_order_loc(&self, price_lots: LotBalance, seq: SequenceNumber) -> Result<usize, usize> {
+        let key = self.sort_key(price_lots, seq);
+        let loc = self.orders.binary_search_by_key(&key, |(price, order)| {
+            self.sort_key(*price, order.sequence_number)
+        });
+
+        // binary search silently returns garbage on an unsorted vec. check
+        // that the neighbors of the result bracket the key
+        #[cfg(any(test, feature = "integrity_checks"))]
+        {
+            let key_at = |i: usize| {
+                let (price, order) = &self.orders[i];
+                self.sort_key(*price, order.sequence_number)
+            };
+            let consistent = match loc {
+                Ok(i) => key_at(i) == key,
+                Err(i) => {
+                    (i == 0 || key_at(i - 1) < key) && (i == self.orders.len() || key < key_at(i))
+                }
+            };
+            tonic_sdk_macros::_assert!(consistent, "order location inconsistent with sort order");
+        }
+
+        loc
+    }
+
+    /// Key the orders are sorted by. Reversed by price only for bids;
+    /// sequence numbers are always ascending.
+    fn sort_key(
+        &self,
+        price_lots: LotBalance,
+        seq: SequenceNumber,
+    ) -> (LotBalance, SequenceNumber) {
         if self.reverse_prices {
-            self.orders
-                // reverse by price only; sequence numbers still need to be in order
-                .binary_search_by_key(&(!price_lots, seq), |(price, order)| {
-                    (!*price, order.sequence_number)
-                })
+            (!price_lots, seq)
         } else {
-            self.orders
-                .binary_search_by_key(&(price_lots, seq), |(price, order)| {
-                    (*price, order.sequence_number)
-                })
+            (price_lots, seq)
+        }
+    }
+
+    /// Check that orders are strictly sorted by price (descending for bids,
+    /// ascending for asks), then by sequence number. Everything else on this
+    /// struct assumes this holds. Intended for tests and fuzzing.
+    pub fn verify_invariants(&self) -> Result<(), &'static str> {
+        let unsorted = self.orders.windows(2).any(|w| {
+            let (a, b) = (&w[0], &w[1]);
+            self.sort_key(a.0, a.1.sequence_number) >= self.sort_key(b.0, b.1.sequence_number)
+        });
+        if unsorted {
+            Err("orders not sorted by price and sequence number")
+        } else {
+            Ok(())
         }
     }
 
@@ -181,9 +313,9 @@ impl VecL2 {
         price_levels.dedup();
 
         if self.reverse_prices {
-            price_levels.binary_search_by_key(&(!price_lots), |price| (!*price))
+            price_levels.binary_search_by_key(&(!price_lots), |price| !*price)
         } else {
-            price_levels.binary_search_by_key(&(price_lots), |price| (*price))
+            price_levels.binary_search_by_key(&(price_lots), |price| *price)
         }
     }
 }
@@ -293,4 +425,154 @@ mod tests {
         assert_eq!(l2.get_price_rank(2), 1, "wrong price rank for price 2");
         assert_eq!(l2.get_price_rank(1), 2, "wrong price rank for price 1");
     }
+
+    #[test]
+    fn take_depth_stops_at_depth() {
+        let mut l2 = VecL2::new(false);
+        l2.save_order(make_order(1, 1));
+        l2.save_order(make_order(2, 2));
+        l2.save_order(make_order(2, 3));
+        l2.save_order(make_order(3, 4));
+
+        let levels = l2.take_depth(1);
+        assert_eq!(levels.len(), 1, "returned a level past the depth");
+        assert_eq!(levels[0].0, 1);
+
+        let levels = l2.take_depth(2);
+        assert_eq!(levels.len(), 2, "returned a level past the depth");
+        assert_eq!(levels[1].0, 2);
+        assert_eq!(levels[1].1.len(), 2);
+
+        assert_eq!(l2.take_depth(5).len(), 3);
+    }
+
+    #[test]
+    fn peek_matches_min_max_order() {
+        fn assert_agrees(l2: &VecL2) {
+            let max = l2.max_order().unwrap();
+            let peek_max = l2.peek_max().unwrap();
+            assert_eq!(peek_max.price_lots, max.unwrap_price());
+            assert_eq!(peek_max.open_qty_lots, max.open_qty_lots);
+            assert_eq!(*peek_max.owner_id, max.owner_id);
+            assert_eq!(
+                l2.get_order(peek_max.price_lots, max.sequence_number)
+                    .unwrap()
+                    .sequence_number,
+                max.sequence_number
+            );
+
+            let min = l2.min_order().unwrap();
+            let peek_min = l2.peek_min().unwrap();
+            assert_eq!(peek_min.price_lots, min.unwrap_price());
+            assert_eq!(peek_min.open_qty_lots, min.open_qty_lots);
+            assert_eq!(*peek_min.owner_id, min.owner_id);
+        }
+
+        for reverse_prices in [true, false] {
+            let mut l2 = VecL2::new(reverse_prices);
+            assert!(l2.peek_max().is_none());
+            assert!(l2.peek_min().is_none());
+
+            // distinguish orders at the same price by quantity
+            for (price, seq) in [(2, 1), (1, 2), (3, 3), (1, 4), (3, 5), (2, 6)] {
+                let mut order = make_order(price, seq);
+                order.open_qty_lots = seq;
+                l2.save_order(order);
+                assert_agrees(&l2);
+            }
+        }
+    }
+
+    #[test]
+    fn with_capacity() {
+        let mut l2 = VecL2::with_capacity(true, 16);
+        assert!(l2.orders.capacity() >= 16, "capacity not reserved");
+        assert!(l2.is_empty());
+
+        let mut reference = VecL2::new(true);
+        for (price, seq) in [(1, 1), (2, 2), (1, 3)] {
+            l2.save_order(make_order(price, seq));
+            reference.save_order(make_order(price, seq));
+        }
+        assert_eq!(
+            l2.iter().map(|o| o.id()).collect::<Vec<_>>(),
+            reference.iter().map(|o| o.id()).collect::<Vec<_>>(),
+            "preallocated side sorted differently"
+        );
+    }
+
+    #[test]
+    fn take_depth_ordered() {
+        fn prices(levels: Vec<(LotBalance, Vec<OpenLimitOrder>)>) -> Vec<LotBalance> {
+            levels.into_iter().map(|(p, _)| p).collect()
+        }
+
+        // bid side; best to worst is descending
+        let mut l2 = VecL2::new(true);
+        l2.save_order(make_order(1, 1));
+        l2.save_order(make_order(3, 2));
+        l2.save_order(make_order(2, 3));
+        l2.save_order(make_order(3, 4));
+
+        assert_eq!(prices(l2.take_depth(3)), vec![3, 2, 1]);
+        assert_eq!(prices(l2.take_depth_ordered(3, false)), vec![3, 2, 1]);
+        assert_eq!(prices(l2.take_depth_ordered(3, true)), vec![1, 2, 3]);
+        // only the best levels are included
+        assert_eq!(prices(l2.take_depth_ordered(2, true)), vec![2, 3]);
+        // orders within a level keep time priority
+        let levels = l2.take_depth_ordered(3, true);
+        let (_, top) = levels.last().unwrap();
+        assert_eq!(
+            top.iter().map(|o| o.sequence_number).collect::<Vec<_>>(),
+            vec![2, 4]
+        );
+
+        // ask side; best to worst is ascending
+        let mut l2 = VecL2::new(false);
+        l2.save_order(make_order(1, 1));
+        l2.save_order(make_order(3, 2));
+        l2.save_order(make_order(2, 3));
+
+        assert_eq!(prices(l2.take_depth_ordered(3, true)), vec![1, 2, 3]);
+        assert_eq!(prices(l2.take_depth_ordered(3, false)), vec![3, 2, 1]);
+        assert_eq!(prices(l2.take_depth_ordered(2, false)), vec![2, 1]);
+    }
+
+    #[test]
+    fn save_order_update_vs_insert() {
+        let mut l2 = VecL2::new(false);
+        assert!(l2.find_order_loc(1, 1).is_err(), "fresh insert");
+        l2.save_order(make_order(1, 1));
+        assert!(l2.find_order_loc(1, 2).is_err(), "fresh insert");
+        l2.save_order(make_order(1, 2));
+
+        // updating an existing order overwrites its slot
+        assert_eq!(l2.find_order_loc(1, 1), Ok(0));
+        let mut updated = make_order(1, 1);
+        updated.open_qty_lots = 5;
+        l2.save_order(updated);
+        assert_eq!(l2.orders.len(), 2);
+        assert_eq!(l2.orders[0].1.open_qty_lots, 5);
+        assert_eq!(l2.orders[1].1.sequence_number, 2);
+    }
+
+    #[test]
+    fn verify_invariants_catches_unsorted() {
+        for reverse_prices in [false, true] {
+            let mut l2 = VecL2::new(reverse_prices);
+            l2.save_order(make_order(1, 1));
+            l2.save_order(make_order(2, 2));
+            l2.save_order(make_order(1, 3));
+            assert_eq!(l2.verify_invariants(), Ok(()));
+
+            l2.orders.swap(0, 2);
+            assert!(l2.verify_invariants().is_err());
+        }
+
+        // duplicate keys are also invalid
+        let mut l2 = VecL2::new(false);
+        l2.orders.push((1, make_order(1, 1)));
+        l2.orders.push((1, make_order(1, 1)));
+        assert!(l2.verify_invariants().is_err());
+    }
 }
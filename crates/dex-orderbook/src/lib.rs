@@ -1,15 +1,35 @@
 #[cfg(test)]
 mod tests;
 
+mod events;
 pub mod l2;
+pub mod market;
+#[cfg(any(test, feature = "mm_protection"))]
+pub mod mm_protection;
 pub mod orderbook;
 pub mod orderbook_math;
+pub mod params;
+mod replay;
+pub mod sequence;
+#[cfg(any(test, feature = "stats"))]
+pub mod stats;
+pub mod view;
 
 pub use l2::*;
+pub use market::*;
+#[cfg(any(test, feature = "mm_protection"))]
+pub use mm_protection::*;
 pub use orderbook::*;
 pub use orderbook_math::*;
+pub use params::*;
+pub use sequence::*;
+#[cfg(any(test, feature = "stats"))]
+pub use stats::*;
+pub use view::*;
 
+use l2::btree::BTreeL2;
 use l2::vec::VecL2;
+use near_sdk::AccountId;
 
 pub type ClientId = u32;
 
@@ -22,3 +42,33 @@ impl Default for VecOrderbook {
         Self::new(bids, asks)
     }
 }
+
+pub type BTreeOrderbook = Orderbook<BTreeL2>;
+
+impl Default for BTreeOrderbook {
+    fn default() -> Self {
+        let bids = BTreeL2::new(true);
+        let asks = BTreeL2::new(false);
+        Self::new(bids, asks)
+    }
+}
+
+impl VecOrderbook {
+    /// Create an empty orderbook with room for `cap` orders preallocated on
+    /// each side.
+    pub fn with_capacity(cap: usize) -> Self {
+        let bids = VecL2::with_capacity(true, cap);
+        let asks = VecL2::with_capacity(false, cap);
+        Self::new(bids, asks)
+    }
+
+    /// Create an orderbook by placing each order in sequence on behalf of
+    /// `user_id`. Convenient for setting up books in tests.
+    pub fn from_orders(user_id: &AccountId, orders: impl IntoIterator<Item = NewOrder>) -> Self {
+        let mut ob = Self::default();
+        for order in orders {
+            ob.place_order(user_id, order);
+        }
+        ob
+    }
+}
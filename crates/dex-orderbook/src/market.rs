@@ -0,0 +1,146 @@
+/// An orderbook bundled with the lot sizes used to convert to and from native
+/// amounts.
+use std::ops::{Deref, DerefMut};
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::{AccountId, Balance, IntoStorageKey};
+use tonic_sdk_dex_types::{MarketId, OrderId, SequenceNumber, Side};
+
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Market {
+    pub orderbook: VecOrderbook,
+    pub calculator: OrderbookCalculator,
+}
+
+impl Market {
+    /// Create a market with an empty book.
+    pub fn new(
+        base_lot_size: Balance,
+        quote_lot_size: Balance,
+        base_denomination: Balance,
+    ) -> Self {
+        Self {
+            orderbook: VecOrderbook::default(),
+            calculator: OrderbookCalculator {
+                base_lot_size,
+                quote_lot_size,
+                base_denomination,
+            },
+        }
+    }
+
+    /// Convert order params to lots and place the order.
+    pub fn place(
+        &mut self,
+        user_id: &AccountId,
+        params: NewOrderParams,
+        sequence_number: SequenceNumber,
+    ) -> PlaceOrderResult {
+        let order = params.into_new_order(&self.calculator, sequence_number);
+        self.orderbook.place_order(user_id, order)
+    }
+
+    pub fn cancel(&mut self, order_id: OrderId) -> Option<CancelOrderResult> {
+        self.orderbook.cancel_order(order_id)
+    }
+
+    /// Best price resting on a side in native quote per whole base. [None] if
+    /// the side is empty.
+    pub fn best_price_native(&self, side: Side) -> Option<Balance> {
+        self.orderbook
+            .best_price_lots(side)
+            .map(|p| conv::lots_to_balance(p) * self.calculator.quote_lot_size)
+    }
+
+    /// Average of the best bid and best ask in native quote. If one side is
+    /// empty, the best price on the other side. [None] if the book is empty.
+    pub fn midmarket_price_native(&self) -> Option<Balance> {
+        match (
+            self.best_price_native(Side::Buy),
+            self.best_price_native(Side::Sell),
+        ) {
+            (Some(bid), Some(ask)) => Some(bid / 2 + ask / 2 + (bid % 2 + ask % 2) / 2),
+            (Some(price), None) | (None, Some(price)) => Some(price),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Markets stored on the trie, keyed by ID.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Markets {
+    markets: UnorderedMap<MarketId, Market>,
+}
+
+impl Markets {
+    pub fn new<S: IntoStorageKey>(prefix: S) -> Self {
+        Self {
+            markets: UnorderedMap::new(prefix),
+        }
+    }
+
+    /// Read a market from storage.
+    pub fn get(&self, market_id: &MarketId) -> Option<Market> {
+        self.markets.get(market_id)
+    }
+
+    /// Read a market for modification. The market is written back to storage
+    /// when the returned handle is dropped, whether or not it was modified.
+    pub fn get_mut(&mut self, market_id: &MarketId) -> Option<MarketMut<'_>> {
+        let market = self.markets.get(market_id)?;
+        Some(MarketMut {
+            markets: &mut self.markets,
+            market_id: *market_id,
+            market: Some(market),
+        })
+    }
+
+    /// Save a market, returning the previous market with the same ID, if any.
+    pub fn insert(&mut self, market_id: &MarketId, market: &Market) -> Option<Market> {
+        self.markets.insert(market_id, market)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (MarketId, Market)> + '_ {
+        self.markets.iter()
+    }
+
+    pub fn len(&self) -> u64 {
+        self.markets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markets.is_empty()
+    }
+}
+
+/// Handle to a market read from [Markets]. Saves the market when dropped.
+pub struct MarketMut<'a> {
+    markets: &'a mut UnorderedMap<MarketId, Market>,
+    market_id: MarketId,
+    market: Option<Market>,
+}
+
+impl Deref for MarketMut<'_> {
+    type Target = Market;
+
+    fn deref(&self) -> &Market {
+        self.market.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for MarketMut<'_> {
+    fn deref_mut(&mut self) -> &mut Market {
+        self.market.as_mut().unwrap()
+    }
+}
+
+impl Drop for MarketMut<'_> {
+    fn drop(&mut self) {
+        if let Some(market) = self.market.take() {
+            self.markets.insert(&self.market_id, &market);
+        }
+    }
+}
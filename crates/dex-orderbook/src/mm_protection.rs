@@ -0,0 +1,60 @@
+/// Per-maker fill counts for market maker protection.
+///
+/// Only compiled with the `mm_protection` feature. The counts live in memory
+/// and aren't part of the book's borsh state, so they start empty whenever the
+/// book is loaded. Callers that count across calls persist them in their own
+/// storage with [fill_counts](Orderbook::fill_counts) and restore them with
+/// [set_fill_counts](Orderbook::set_fill_counts).
+///
+/// The book has no clock, so the caller defines the time window: check
+/// [owner_fill_count](Orderbook::owner_fill_count) against a threshold after
+/// placing orders, pull the maker's quotes with
+/// [cancel_all_for_owner](Orderbook::cancel_all_for_owner) once it's
+/// exceeded, and call [reset_fill_counts](Orderbook::reset_fill_counts) when
+/// the window ends.
+use std::collections::BTreeMap;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::AccountId;
+
+use crate::*;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct MakerFillCounts {
+    counts: BTreeMap<AccountId, u32>,
+}
+
+impl MakerFillCounts {
+    pub(crate) fn record(&mut self, fill: &Match) {
+        let count = self.counts.entry(fill.maker_user_id.clone()).or_default();
+        *count = count.saturating_add(1);
+    }
+
+    /// Number of fills against the owner's orders since the last reset.
+    pub fn get(&self, owner_id: &AccountId) -> u32 {
+        self.counts.get(owner_id).copied().unwrap_or_default()
+    }
+}
+
+impl<T: L2> Orderbook<T> {
+    /// Number of fills against the owner's resting orders since the last
+    /// [reset_fill_counts](Orderbook::reset_fill_counts).
+    pub fn owner_fill_count(&self, owner_id: &AccountId) -> u32 {
+        self.maker_fills.get(owner_id)
+    }
+
+    /// Start a new window, clearing every maker's fill count.
+    pub fn reset_fill_counts(&mut self) {
+        self.maker_fills = MakerFillCounts::default();
+    }
+
+    /// Fill counts since the book was loaded or the counts were last set.
+    pub fn fill_counts(&self) -> &MakerFillCounts {
+        &self.maker_fills
+    }
+
+    /// Replace the fill counts, eg, with counts saved by an earlier call.
+    pub fn set_fill_counts(&mut self, counts: MakerFillCounts) {
+        self.maker_fills = counts;
+    }
+}
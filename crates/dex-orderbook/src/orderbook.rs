@@ -3,6 +3,7 @@ use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::U128;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{AccountId, Balance};
+use sha3::{Digest, Sha3_256};
 use std::fmt::Debug;
 
 use tonic_sdk_dex_errors as errors;
@@ -21,10 +22,12 @@ pub enum OrderOutcome {
     /// The order was completely filled and not placed on the book.
     Filled,
 
-    /// The order was partially filled. The remainder was placed on the book.
+    /// The order was partially filled. The remainder was placed on the book,
+    /// or cancelled if the order type doesn't post.
     PartialFill,
 
-    /// The order was cancelled.
+    /// The order was cancelled without filling, eg, an immediate-or-cancel
+    /// order that didn't cross.
     Cancelled,
 
     /// The order was placed on the book. No part of the order was immediately
@@ -38,7 +41,7 @@ pub enum OrderOutcome {
 
 /// Internal struct representing an order ready to be processed by the matching
 /// engine.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct NewOrder {
     pub sequence_number: SequenceNumber,
     pub limit_price_lots: Option<LotBalance>,
@@ -48,6 +51,30 @@ pub struct NewOrder {
     /// Maximum amount to buy. This is one of the ways to control stopping
     /// behavior along with `available_quote_lots`
     pub max_qty_lots: LotBalance,
+    /// Stop matching once the trade price moves more than this many bps away
+    /// from the first fill price. Intended for [OrderType::Market] orders,
+    /// which have no limit price to protect against sweeping a thin book. If
+    /// matching stops at the band, the remainder of other order types isn't
+    /// posted, since it would still cross the book.
+    pub max_impact_bps: Option<u16>,
+    /// Stop matching after this many maker fills. Bounds the cost of settling
+    /// the order. If matching stops at the cap, the remainder is never posted,
+    /// since it would cross the book.
+    pub max_matches: Option<usize>,
+    /// Reject the order if it would post at a new price level while its side
+    /// of the book already has this many levels. If the order filled in part,
+    /// the fills stand and only the remainder is not posted. Orders at
+    /// existing levels are unaffected. Bounds the cost of depth and rank
+    /// computations.
+    pub max_price_levels: Option<u32>,
+    /// Size a sell by the quote it should receive instead of by base. The
+    /// notional is converted to base lots at the limit price (the best bid
+    /// for orders without one), and `max_qty_lots` is capped at the result,
+    /// so set `max_qty_lots` to [LotBalance::MAX] to size by notional alone;
+    /// leaving it at 0 rejects the order. A notional worth less than one lot
+    /// also rejects the order. Ignored for buys, which are sized by
+    /// `available_quote_lots`.
+    pub notional_quote_lots: Option<LotBalance>,
     pub side: Side,
     pub order_type: OrderType,
     pub base_denomination: u128,
@@ -71,6 +98,35 @@ impl NewOrder {
         }
     }
 
+    /// Check the order parameters without panicking.
+    pub fn validate(&self) -> Result<(), PlaceOrderError> {
+        if self.order_type != OrderType::Market {
+            match self.limit_price_lots {
+                None => return Err(PlaceOrderError::MissingLimitPrice),
+                Some(0) => return Err(PlaceOrderError::ZeroLimitPrice),
+                _ => {}
+            }
+        }
+        if self.max_qty_lots == 0 {
+            return Err(PlaceOrderError::ZeroQuantity);
+        }
+        Ok(())
+    }
+
+    /// Clear fields that don't apply to the order's side. Sells are sized in
+    /// base, so a quote budget on a sell is dropped (and logged with the
+    /// `debug_log` feature, since it likely means a caller bug) instead of
+    /// limiting the fill as though the order were a buy.
+    pub fn normalize(&mut self) {
+        if self.side == Side::Sell && self.available_quote_lots.is_some() {
+            debug_log!(
+                "ignoring available_quote_lots {:?} on sell",
+                self.available_quote_lots
+            );
+            self.available_quote_lots = None;
+        }
+    }
+
     pub fn assert_valid(&self) {
         if self.order_type != OrderType::Market {
             let limit_price = _expect!(self.limit_price_lots, "missing limit price");
@@ -80,6 +136,86 @@ impl NewOrder {
     }
 }
 
+/// Split an order into children of at most `max_qty_per_child` lots, eg, to
+/// work around `max_matches` or `max_price_levels` caps. Child quantities sum
+/// to the parent's, and quote budgets (`available_quote_lots` and
+/// `notional_quote_lots`) are split in proportion to quantity, summing
+/// exactly to the parent's.
+///
+/// Children keep the parent's sequence number. The caller must assign each
+/// child a new one before placing it.
+pub fn split_order(order: NewOrder, max_qty_per_child: LotBalance) -> Vec<NewOrder> {
+    _assert!(max_qty_per_child > 0, "max qty per child is 0");
+    if order.max_qty_lots == 0 {
+        return vec![order];
+    }
+    let total_qty = order.max_qty_lots as u128;
+    // budget for the first `qty` lots, so that rounding never accumulates
+    let budget_until = |budget: Option<LotBalance>, qty: LotBalance| {
+        budget.map(|b| (b as u128 * qty as u128 / total_qty) as LotBalance)
+    };
+
+    let mut children = vec![];
+    let mut filled: LotBalance = 0;
+    loop {
+        let qty = max_qty_per_child.min(order.max_qty_lots - filled);
+        let sub = |budget| {
+            budget_until(budget, filled + qty)
+                .zip(budget_until(budget, filled))
+                .map(|(hi, lo)| hi - lo)
+        };
+        children.push(NewOrder {
+            max_qty_lots: qty,
+            available_quote_lots: sub(order.available_quote_lots),
+            notional_quote_lots: sub(order.notional_quote_lots),
+            ..order.clone()
+        });
+        filled += qty;
+        if filled == order.max_qty_lots {
+            break;
+        }
+    }
+    children
+}
+
+/// Reason an order couldn't be placed. Returned by
+/// [try_place_order](Orderbook::try_place_order).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaceOrderError {
+    /// Non-market order without a limit price.
+    MissingLimitPrice,
+    /// Non-market order with a limit price of 0.
+    ZeroLimitPrice,
+    /// Order for 0 lots.
+    ZeroQuantity,
+    /// Order would match against another order from the same account.
+    SelfTrade,
+    /// Limit price doesn't fit in a [LotBalance] once converted to lots.
+    PriceOverflow,
+    /// Owner already has a resting order with the same client ID.
+    DuplicateClientId,
+}
+
+impl PlaceOrderError {
+    /// The error message used when the error is raised as a panic.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlaceOrderError::MissingLimitPrice => errors::MISSING_LIMIT_PRICE,
+            PlaceOrderError::ZeroLimitPrice => errors::ZERO_LIMIT_PRICE,
+            PlaceOrderError::ZeroQuantity => errors::ZERO_ORDER_AMOUNT,
+            PlaceOrderError::SelfTrade => errors::SELF_TRADE,
+            PlaceOrderError::PriceOverflow => errors::PRICE_OVERFLOW,
+            PlaceOrderError::DuplicateClientId => errors::DUPLICATE_CLIENT_ID,
+        }
+    }
+}
+
+impl std::fmt::Display for PlaceOrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Internal struct representing a match ready to be executed.
 #[derive(Clone, Debug)]
 pub struct Match {
@@ -88,6 +224,8 @@ pub struct Match {
     pub fill_qty_lots: LotBalance,
     pub fill_price_lots: LotBalance,
     pub native_quote_paid: Balance,
+    /// Amount of base traded in the match, in native units.
+    pub native_base_filled: Balance,
     pub maker_order_price_rank: u32,
 
     /// Was the matched maker order removed. Used to update [Account]'s
@@ -99,6 +237,52 @@ impl Match {
     pub fn did_remove_maker_order(&self) -> bool {
         self.maker_order_removed.unwrap()
     }
+
+    /// Same as [did_remove_maker_order](Match::did_remove_maker_order), but
+    /// returns [None] instead of panicking if the match hasn't been executed
+    /// yet.
+    pub fn fully_filled_maker(&self) -> Option<bool> {
+        self.maker_order_removed
+    }
+
+    /// Native balance changes of the taker and maker resulting from the
+    /// match, before fees. The taker buys base if `taker_side` is
+    /// [Side::Buy] and sells it otherwise; the maker takes the other side.
+    pub fn settlement(&self, taker_side: Side) -> MatchSettlement {
+        let base = self.native_base_filled as i128;
+        let quote = self.native_quote_paid as i128;
+        let buyer = BalanceDelta {
+            base,
+            quote: -quote,
+        };
+        let seller = BalanceDelta { base: -base, quote };
+        match taker_side {
+            Side::Buy => MatchSettlement {
+                taker: buyer,
+                maker: seller,
+            },
+            Side::Sell => MatchSettlement {
+                taker: seller,
+                maker: buyer,
+            },
+        }
+    }
+}
+
+/// Change to an account's native balances. Positive amounts are credited,
+/// negative amounts are debited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BalanceDelta {
+    pub base: i128,
+    pub quote: i128,
+}
+
+/// Balance changes for both parties of a [Match]. See
+/// [settlement](Match::settlement).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchSettlement {
+    pub taker: BalanceDelta,
+    pub maker: BalanceDelta,
 }
 
 /// Result of running the matching engine. Used to settle account balance
@@ -121,18 +305,59 @@ pub struct PlaceOrderResult {
     /// Best resting ask before the order was placed. [None] if ask side was
     /// empty.
     pub best_ask: Option<LotBalance>,
+    /// Client ID from the order, if any.
+    pub client_id: Option<ClientId>,
+    /// True if a buy stopped short of `max_qty_lots` because its
+    /// `available_quote_lots` ran out. A buy that stops short is reported as
+    /// [OrderOutcome::PartialFill] either way, so check this to tell whether
+    /// the budget or the book's liquidity stopped it.
+    pub budget_exhausted: bool,
 }
 
 impl PlaceOrderResult {
     pub fn is_posted(&self) -> bool {
         self.open_qty_lots > 0
     }
+
+    /// Return true if the order changed any state that has to be settled,
+    /// ie, it filled, posted, or has unused funds to refund. Rejected orders
+    /// leave the book and the user's account untouched.
+    pub fn requires_settlement(&self) -> bool {
+        self.outcome != OrderOutcome::Rejected
+    }
+
+    /// Fold the result of a later order for the same logical parent order,
+    /// eg, the next slice of a TWAP, into this one.
+    ///
+    /// Fills, quote spent, and matches accumulate. The ID and client ID of
+    /// the first order are kept, while open quantity, price rank, best prices,
+    /// and `budget_exhausted` describe the latest order. The outcome is the
+    /// latest order's, except that it's [OrderOutcome::PartialFill] if the
+    /// latest order didn't fill but an earlier one did.
+    pub fn merge(&mut self, other: PlaceOrderResult) {
+        self.fill_qty_lots += other.fill_qty_lots;
+        self.quote_amount_lots += other.quote_amount_lots;
+        self.matches.extend(other.matches);
+        self.open_qty_lots = other.open_qty_lots;
+        self.price_rank = other.price_rank;
+        self.best_bid = other.best_bid;
+        self.best_ask = other.best_ask;
+        self.budget_exhausted = other.budget_exhausted;
+        self.outcome = match other.outcome {
+            OrderOutcome::Posted | OrderOutcome::Cancelled | OrderOutcome::Rejected
+                if self.fill_qty_lots > 0 =>
+            {
+                OrderOutcome::PartialFill
+            }
+            outcome => outcome,
+        };
+    }
 }
 
 impl ValueLocked for PlaceOrderResult {
     fn value_locked(
         &self,
-        base_lot_size: Balance,
+        _base_lot_size: Balance,
         _quote_lot_size: Balance,
         _base_denomination: Balance,
     ) -> Tvl {
@@ -140,11 +365,7 @@ impl ValueLocked for PlaceOrderResult {
         // calculate from the *_lots fields
         // calculate diff... :skull:
         let quote_traded = self.matches.iter().map(|m| m.native_quote_paid).sum();
-        let base_traded = self
-            .matches
-            .iter()
-            .map(|m| m.fill_qty_lots as u128 * base_lot_size)
-            .sum();
+        let base_traded = self.matches.iter().map(|m| m.native_base_filled).sum();
 
         Tvl {
             quote_locked: quote_traded,
@@ -167,6 +388,10 @@ pub struct PlaceOrderResultView {
 
     /// Amount of base still open.
     pub open_quantity: U128,
+
+    /// Client ID from the order, if any. Lets clients match the result to
+    /// their pending order without knowing the sequence number in advance.
+    pub client_id: Option<ClientId>,
 }
 
 impl PlaceOrderResult {
@@ -177,6 +402,7 @@ impl PlaceOrderResult {
             base_fill_quantity: U128::from(self.fill_qty_lots as u128 * base_lot_size),
             open_quantity: U128::from(self.open_qty_lots as u128 * base_lot_size),
             quote_fill_quantity: U128::from(self.quote_amount_lots as u128 * quote_lot_size),
+            client_id: self.client_id,
         }
     }
 }
@@ -185,13 +411,49 @@ impl PlaceOrderResult {
 pub struct Orderbook<T: L2> {
     pub bids: T,
     pub asks: T,
+    #[cfg(any(test, feature = "stats"))]
+    stats: OrderbookStats,
+    #[cfg(any(test, feature = "mm_protection"))]
+    #[borsh_skip]
+    pub(crate) maker_fills: MakerFillCounts,
+}
+
+/// Result of [estimate_slippage](Orderbook::estimate_slippage). Prices are in
+/// native quote per whole base token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlippageEstimate {
+    /// Average fill price, rounded down.
+    pub avg_price: Balance,
+    /// Price of the last level the order reaches.
+    pub worst_price: Balance,
+    /// Number of price levels the order reaches.
+    pub levels_crossed: usize,
+}
+
+/// Options for [place_order_opts](Orderbook::place_order_opts).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlaceOpts {
+    /// Compute the result without modifying the book.
+    pub dry_run: bool,
+    /// Reject the order with [PlaceOrderError::DuplicateClientId] if the
+    /// owner already has a resting order with the same client ID.
+    pub reject_duplicate_client_id: bool,
 }
 
 #[derive(Debug)]
 pub struct MatchOrderResult {
     unfilled_qty_lots: LotBalance,
-    unused_quote_lots: Option<LotBalance>,
-    matches: Vec<Match>,
+    /// Native quote left over from a buy's budget. Kept in native units so
+    /// large budgets aren't truncated to lots while matching.
+    pub(crate) unused_quote_native: Option<Balance>,
+    pub(crate) matches: Vec<Match>,
+    /// True if matching stopped at `max_matches` with crossing orders left.
+    hit_match_cap: bool,
+    /// True if matching stopped at the `max_impact_bps` band with crossing
+    /// orders left.
+    hit_impact_band: bool,
+    /// True if a buy's remaining quote limited a fill.
+    budget_exhausted: bool,
 }
 
 #[derive(Debug)]
@@ -203,7 +465,66 @@ pub struct CancelOrderResult {
 
 impl<T: L2> Orderbook<T> {
     pub fn new(bids: T, asks: T) -> Self {
-        Self { bids, asks }
+        Self {
+            bids,
+            asks,
+            #[cfg(any(test, feature = "stats"))]
+            stats: OrderbookStats::default(),
+            #[cfg(any(test, feature = "mm_protection"))]
+            maker_fills: MakerFillCounts::default(),
+        }
+    }
+
+    /// Lifetime volume and trade count.
+    #[cfg(any(test, feature = "stats"))]
+    pub fn stats(&self) -> OrderbookStats {
+        self.stats
+    }
+}
+
+/// Books are equal if both sides hold the same resting orders. Stats, if
+/// enabled, aren't compared.
+impl<T: L2 + PartialEq> PartialEq for Orderbook<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bids == other.bids && self.asks == other.asks
+    }
+}
+
+impl<T: L2 + Eq> Eq for Orderbook<T> {}
+
+impl<T: L2> Orderbook<T> {
+    /// Same as [place_order](Orderbook::place_order), with options. With
+    /// `dry_run` set, the order is matched without applying the fills or
+    /// posting the order, so the returned result is exactly what placing the
+    /// order would produce but the book is left unchanged.
+    pub fn place_order_opts(
+        &mut self,
+        user_id: &AccountId,
+        order: NewOrder,
+        opts: PlaceOpts,
+    ) -> PlaceOrderResult {
+        if opts.reject_duplicate_client_id {
+            if let Err(e) = self.check_client_id(user_id, &order) {
+                near_sdk::env::panic_str(e.as_str());
+            }
+        }
+        self.execute_order(user_id, order, opts.dry_run)
+            .unwrap_or_else(|e| near_sdk::env::panic_str(e.as_str()))
+    }
+
+    /// Same as [try_place_order](Orderbook::try_place_order), with options.
+    /// See [place_order_opts](Orderbook::place_order_opts).
+    pub fn try_place_order_opts(
+        &mut self,
+        user_id: &AccountId,
+        order: NewOrder,
+        opts: PlaceOpts,
+    ) -> Result<PlaceOrderResult, PlaceOrderError> {
+        if opts.reject_duplicate_client_id {
+            self.check_client_id(user_id, &order)?;
+        }
+        order.validate()?;
+        self.execute_order(user_id, order, opts.dry_run)
     }
 }
 
@@ -255,24 +576,358 @@ impl<T: L2> Orderbook<T> {
         }
     }
 
-    fn insert_order(&mut self, order: OpenLimitOrder) {
-        match order.unwrap_side() {
-            Side::Buy => self.bids.save_order(order),
-            Side::Sell => self.asks.save_order(order),
+    /// Return true if the account has at least one order resting on either
+    /// side. Stops at the first match.
+    pub fn has_orders_for(&self, owner_id: &AccountId) -> bool {
+        self.bids.iter().any(|o| o.owner_id == *owner_id)
+            || self.asks.iter().any(|o| o.owner_id == *owner_id)
+    }
+
+    /// Return the worst price resting on a side, ie, the lowest bid or the
+    /// highest ask. [None] if the side is empty.
+    pub fn worst_price_lots(&self, side: Side) -> Option<LotBalance> {
+        match side {
+            Side::Buy => self.bids.peek_min(),
+            Side::Sell => self.asks.peek_max(),
+        }
+        .map(|o| o.price_lots)
+    }
+
+    /// Same as [take_depth](TakeL2Depth::take_depth) on one side of the book,
+    /// but only including orders owned by `owner_id`. Levels where the owner
+    /// has no orders are skipped and don't count toward `depth`.
+    pub fn take_depth_for_owner(
+        &self,
+        owner_id: &AccountId,
+        depth: usize,
+        side: Side,
+    ) -> Vec<(LotBalance, Vec<OpenLimitOrder>)> {
+        let orders = self.side(side).iter();
+        group_by_price(orders.filter(|o| o.owner_id == *owner_id), depth)
+    }
+
+    /// Return true if the best bid is at or above the best ask. This should
+    /// never be the case for a book modified only by the matching engine.
+    pub fn is_crossed(&self) -> bool {
+        match (
+            self.best_price_lots(Side::Buy),
+            self.best_price_lots(Side::Sell),
+        ) {
+            (Some(bid), Some(ask)) => bid >= ask,
+            _ => false,
+        }
+    }
+
+    /// Order-flow imbalance over the top `depth` price levels of each side, in
+    /// bps: `(bid_qty - ask_qty) / (bid_qty + ask_qty)`, ranging from -10000
+    /// (asks only) to 10000 (bids only). Uses integer math so it's
+    /// deterministic on-chain. [None] if both sides are empty.
+    pub fn imbalance(&self, depth: usize) -> Option<i16> {
+        let side_qty = |l2: &T| -> i128 {
+            l2.take_depth(depth)
+                .iter()
+                .flat_map(|(_, orders)| orders.iter())
+                .map(|o| o.open_qty_lots as i128)
+                .sum()
         };
+        let bid_qty = side_qty(&self.bids);
+        let ask_qty = side_qty(&self.asks);
+        let total = bid_qty + ask_qty;
+        if total == 0 {
+            return None;
+        }
+        Some(((bid_qty - ask_qty) * BPS_DIVISOR as i128 / total) as i16)
     }
 
-    fn get_price_rank(&self, side: Side, price_lots: LotBalance) -> u32 {
+    /// Estimate the cost of immediately trading `qty_lots` on `side` by
+    /// walking the opposite side of the book, without modifying it. [None] if
+    /// the book doesn't have enough resting quantity to fill the order.
+    pub fn estimate_slippage(
+        &self,
+        side: Side,
+        qty_lots: LotBalance,
+        calc: &OrderbookCalculator,
+    ) -> Option<SlippageEstimate> {
+        if qty_lots == 0 {
+            return None;
+        }
+        let book_side = match side {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+
+        // walk orders lazily, valuing each level's fill as a whole so that
+        // rounding matches a single fill at that price
+        let mut remaining = qty_lots;
+        let mut quote_total: Balance = 0;
+        let mut worst_price_lots = 0;
+        let mut levels_crossed = 0;
+        let mut level_fill_qty = 0;
+        for order in self.side(book_side).iter() {
+            let price_lots = order.unwrap_price();
+            if levels_crossed > 0 && price_lots != worst_price_lots {
+                quote_total += calc.get_bid_quote_value(level_fill_qty, worst_price_lots);
+                level_fill_qty = 0;
+            }
+            if levels_crossed == 0 || price_lots != worst_price_lots {
+                worst_price_lots = price_lots;
+                levels_crossed += 1;
+            }
+            let fill_qty = order.open_qty_lots.min(remaining);
+            level_fill_qty += fill_qty;
+            remaining -= fill_qty;
+            if remaining == 0 {
+                break;
+            }
+        }
+        if remaining > 0 {
+            return None;
+        }
+        quote_total += calc.get_bid_quote_value(level_fill_qty, worst_price_lots);
+
+        let avg_price = BN!(quote_total)
+            .mul(calc.base_denomination)
+            .div(conv::lots_to_balance(qty_lots) * calc.base_lot_size)
+            .as_u128();
+        Some(SlippageEstimate {
+            avg_price,
+            worst_price: conv::lots_to_balance(worst_price_lots) * calc.quote_lot_size,
+            levels_crossed,
+        })
+    }
+
+    /// Quantity a taker on `side` has to trade to reach `target_price_lots`,
+    /// ie, the total resting on the opposite side at prices up to and
+    /// including the target (down to it, for a sell). If the target is beyond
+    /// the book, this is everything resting on the opposite side.
+    pub fn qty_to_reach_price(&self, side: Side, target_price_lots: LotBalance) -> LotBalance {
+        let (book_side, reaches): (Side, fn(LotBalance, LotBalance) -> bool) = match side {
+            Side::Buy => (Side::Sell, |price, target| price <= target),
+            Side::Sell => (Side::Buy, |price, target| price >= target),
+        };
+        self.side(book_side)
+            .iter()
+            .take_while(|o| reaches(o.unwrap_price(), target_price_lots))
+            .map(|o| o.open_qty_lots)
+            .sum()
+    }
+
+    /// Highest sequence number resting on either side. [None] if the book is
+    /// empty. After migrating orders with
+    /// [insert_preserving_priority](Orderbook::insert_preserving_priority),
+    /// start the new book's sequence counter above this to avoid reissuing an
+    /// order ID.
+    pub fn max_sequence_number(&self) -> Option<SequenceNumber> {
+        self.bids
+            .iter()
+            .chain(self.asks.iter())
+            .map(|o| o.sequence_number)
+            .max()
+    }
+
+    /// Price rank of every resting order, bids first, each side best price
+    /// first. Use instead of looking up ranks one order at a time, which
+    /// costs a pass over the side per order.
+    pub fn ranked_snapshot(&self) -> Vec<(OrderId, u32)> {
+        let mut ranks = self.bids.price_ranks();
+        ranks.extend(self.asks.price_ranks());
+        ranks
+    }
+
+    /// Value locked at each price level, bids then asks, each side best price
+    /// first. The totals sum to the book's
+    /// [value_locked](ValueLocked::value_locked).
+    pub fn value_locked_by_level(
+        &self,
+        calc: &OrderbookCalculator,
+    ) -> Vec<(Side, LotBalance, Tvl)> {
+        [Side::Buy, Side::Sell]
+            .iter()
+            .flat_map(|side| {
+                self.side(*side).take_depth(usize::MAX).into_iter().map(
+                    move |(price_lots, orders)| {
+                        let tvl = orders
+                            .iter()
+                            .map(|o| {
+                                o.value_locked(
+                                    calc.base_lot_size,
+                                    calc.quote_lot_size,
+                                    calc.base_denomination,
+                                )
+                            })
+                            .sum();
+                        (*side, price_lots, tvl)
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Return the best price resting on a side. [None] if the side is empty.
+    pub fn best_price_lots(&self, side: Side) -> Option<LotBalance> {
         match side {
-            Side::Buy => self.bids.get_price_rank(price_lots),
-            Side::Sell => self.asks.get_price_rank(price_lots),
+            Side::Buy => self.bids.peek_max(),
+            Side::Sell => self.asks.peek_min(),
+        }
+        .map(|o| o.price_lots)
+    }
+
+    /// Hash of the resting orders. Books with the same orders, quantities, and
+    /// priority have the same digest.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        for order in self.bids.iter().chain(self.asks.iter()) {
+            hasher.update(order.id().try_to_vec().unwrap());
+            hasher.update(order.owner_id.as_str().as_bytes());
+            hasher.update(order.open_qty_lots.to_le_bytes());
+            hasher.update(order.client_id.try_to_vec().unwrap());
         }
+        hasher.finalize().into()
+    }
+
+    /// Insert a resting order without running the matching engine, keeping its
+    /// sequence number (and therefore its time priority). Intended for
+    /// migrating orders from another book. The order's price and side must be
+    /// initialized.
+    ///
+    /// Panics if an order with the same sequence number is already resting on
+    /// either side.
+    pub fn insert_preserving_priority(&mut self, mut order: OpenLimitOrder) {
+        let seq = order.sequence_number;
+        _assert!(
+            !self
+                .bids
+                .iter()
+                .chain(self.asks.iter())
+                .any(|o| o.sequence_number == seq),
+            "sequence number already in use"
+        );
+        order.price_rank = None;
+        self.insert_order(order);
+    }
+
+    /// The bids or asks, depending on the side.
+    pub fn side(&self, side: Side) -> &T {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    /// Mutable version of [side](Orderbook::side).
+    pub fn side_mut(&mut self, side: Side) -> &mut T {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    pub(crate) fn insert_order(&mut self, order: OpenLimitOrder) {
+        self.side_mut(order.unwrap_side()).save_order(order);
+    }
+
+    fn get_price_rank(&self, side: Side, price_lots: LotBalance) -> u32 {
+        self.side(side).get_price_rank(price_lots)
     }
 
     /// Place a new order and run the matching engine. This modifies the
     /// orderbook and returns a struct containing information needed to settle
     /// account balance changes resulting from the order.
     pub fn place_order(&mut self, user_id: &AccountId, order: NewOrder) -> PlaceOrderResult {
+        self.execute_order(user_id, order, false)
+            .unwrap_or_else(|e| near_sdk::env::panic_str(e.as_str()))
+    }
+
+    /// Same as [place_order](Orderbook::place_order), but validates the order
+    /// and returns an error instead of panicking. The orderbook is unchanged
+    /// if an error is returned.
+    pub fn try_place_order(
+        &mut self,
+        user_id: &AccountId,
+        order: NewOrder,
+    ) -> Result<PlaceOrderResult, PlaceOrderError> {
+        order.validate()?;
+        self.execute_order(user_id, order, false)
+    }
+
+    /// Same as [place_order](Orderbook::place_order), but an order with
+    /// invalid parameters, eg, zero quantity or a missing limit price, is
+    /// returned as [OrderOutcome::Rejected] instead of panicking. Useful for
+    /// batches where one bad order shouldn't abort the rest.
+    pub fn place_order_or_reject(
+        &mut self,
+        user_id: &AccountId,
+        order: NewOrder,
+    ) -> PlaceOrderResult {
+        if order.validate().is_err() {
+            return self.rejected_result(&order);
+        }
+        self.place_order(user_id, order)
+    }
+
+    /// Return [PlaceOrderError::DuplicateClientId] if `user_id` has a resting
+    /// order with the same client ID as `order`. Orders without a client ID
+    /// never conflict.
+    fn check_client_id(
+        &self,
+        user_id: &AccountId,
+        order: &NewOrder,
+    ) -> Result<(), PlaceOrderError> {
+        let client_id = match order.client_id {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let duplicate = self
+            .bids
+            .iter()
+            .chain(self.asks.iter())
+            .any(|o| o.owner_id == *user_id && o.client_id == Some(client_id));
+        if duplicate {
+            Err(PlaceOrderError::DuplicateClientId)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Result for an order that was rejected without modifying the book.
+    fn rejected_result(&self, order: &NewOrder) -> PlaceOrderResult {
+        PlaceOrderResult {
+            id: new_order_id(
+                order.side,
+                order.limit_price_lots.unwrap_or_default(),
+                order.sequence_number,
+            ),
+            fill_qty_lots: 0,
+            open_qty_lots: 0,
+            quote_amount_lots: 0,
+            outcome: OrderOutcome::Rejected,
+            matches: vec![],
+            price_rank: None,
+            best_bid: self.best_price_lots(Side::Buy),
+            best_ask: self.best_price_lots(Side::Sell),
+            client_id: order.client_id,
+            budget_exhausted: false,
+        }
+    }
+
+    /// Match and post the order. With `dry_run`, the book is left unchanged
+    /// and the result describes what placing the order would do.
+    fn execute_order(
+        &mut self,
+        user_id: &AccountId,
+        mut order: NewOrder,
+        dry_run: bool,
+    ) -> Result<PlaceOrderResult, PlaceOrderError> {
+        order.normalize();
+        self.apply_notional(&mut order);
+        if order.side == Side::Sell
+            && order.notional_quote_lots.is_some()
+            && order.max_qty_lots == 0
+        {
+            // notional too small to sell a single lot
+            return Ok(self.rejected_result(&order));
+        }
+
         let order_id = new_order_id(
             order.side,
             order.limit_price_lots.unwrap_or_default(),
@@ -281,34 +936,48 @@ impl<T: L2> Orderbook<T> {
 
         let MatchOrderResult {
             unfilled_qty_lots,
-            unused_quote_lots,
+            unused_quote_native,
             mut matches,
-        } = self.match_order(user_id, &order);
+            hit_match_cap,
+            hit_impact_band,
+            budget_exhausted,
+        } = self.match_order(user_id, &order)?;
+        // unused quote never exceeds the available quote lots, so this fits
+        let unused_quote_lots = unused_quote_native.map(|n| {
+            _expect!(
+                conv::balance_to_lots_checked(n / order.quote_lot_size),
+                "unused quote lots overflow"
+            )
+        });
+
+        // a remainder left by a cap still crosses the book, so it can't post
+        let mut can_post = !matches!(
+            order.order_type,
+            OrderType::FillOrKill | OrderType::ImmediateOrCancel | OrderType::Market
+        ) && !hit_match_cap
+            && !hit_impact_band;
+        // a remainder that would add a price level past the order's cap isn't
+        // posted; the order is only rejected if nothing filled
+        let exceeds_price_levels =
+            can_post && unfilled_qty_lots > 0 && self.exceeds_price_levels(&order);
+        can_post &= !exceeds_price_levels;
 
         let rejected: bool = {
             match order.order_type {
-                OrderType::PostOnly => unfilled_qty_lots < order.max_qty_lots,
+                OrderType::PostOnly => {
+                    unfilled_qty_lots < order.max_qty_lots
+                        || hit_match_cap
+                        || hit_impact_band
+                        || self.exceeds_price_levels(&order)
+                }
                 OrderType::FillOrKill => unfilled_qty_lots > 0, // XXX: this should be cancelled, not rejected
-                _ => false,
+                _ => exceeds_price_levels && matches.is_empty(),
             }
         };
 
         if rejected {
-            // orderbook unchanged
-            let best_bid = self.find_bbo(Side::Buy).map(|o| o.unwrap_price());
-            let best_ask = self.find_bbo(Side::Sell).map(|o| o.unwrap_price());
             // no orderbook state modified at this point, return to cancel
-            return PlaceOrderResult {
-                id: order_id,
-                fill_qty_lots: 0,
-                open_qty_lots: 0,
-                quote_amount_lots: 0,
-                outcome: OrderOutcome::Rejected,
-                matches: vec![],
-                price_rank: None,
-                best_bid,
-                best_ask,
-            };
+            return Ok(self.rejected_result(&order));
         }
 
         // Update resting orders
@@ -316,35 +985,34 @@ impl<T: L2> Orderbook<T> {
         for fill in matches.iter_mut() {
             let mut maker_order = self.get_order(fill.maker_order_id).unwrap();
             maker_order.open_qty_lots -= fill.fill_qty_lots;
+            fill.maker_order_removed = Some(maker_order.open_qty_lots == 0);
+
+            // update running totals
+            fill_qty_lots += fill.fill_qty_lots;
+            if dry_run {
+                continue;
+            }
 
             if maker_order.open_qty_lots == 0 {
-                fill.maker_order_removed = Some(true);
                 self.remove_order(fill.maker_order_id);
             } else {
-                fill.maker_order_removed = Some(false);
-                match maker_order.unwrap_side() {
-                    Side::Buy => self.bids.save_order(maker_order),
-                    Side::Sell => self.asks.save_order(maker_order),
-                }
+                self.insert_order(maker_order);
             }
-
-            // update running totals
-            fill_qty_lots += fill.fill_qty_lots;
+            #[cfg(any(test, feature = "stats"))]
+            self.stats.record(fill);
+            #[cfg(any(test, feature = "mm_protection"))]
+            self.maker_fills.record(fill);
         }
 
-        let can_post = !matches!(
-            order.order_type,
-            OrderType::FillOrKill | OrderType::ImmediateOrCancel | OrderType::Market
-        );
-
         let outcome = match unfilled_qty_lots {
             0 => OrderOutcome::Filled,
-            _ if order.order_type == OrderType::Market => OrderOutcome::Filled,
+            // nothing filled and nothing to post, eg, an IOC that doesn't cross
+            _ if unfilled_qty_lots == order.max_qty_lots && !can_post => OrderOutcome::Cancelled,
             _ if unfilled_qty_lots == order.max_qty_lots && can_post => OrderOutcome::Posted,
             _ => OrderOutcome::PartialFill,
         };
 
-        if unfilled_qty_lots > 0 && can_post {
+        if unfilled_qty_lots > 0 && can_post && !dry_run {
             self.insert_order(OpenLimitOrder {
                 sequence_number: order.sequence_number,
                 owner_id: user_id.clone(),
@@ -359,7 +1027,8 @@ impl<T: L2> Orderbook<T> {
 
         let open_qty_lots = if can_post { unfilled_qty_lots } else { 0 };
 
-        // return price rank if order posted
+        // return price rank if order posted. matches are on the other side, so
+        // the rank is the same whether or not the order was inserted
         let price_rank = if open_qty_lots > 0 {
             Some(self.get_price_rank(
                 order.side,
@@ -370,29 +1039,123 @@ impl<T: L2> Orderbook<T> {
         };
 
         // orderbook has been mutated!
-        let best_bid = self.find_bbo(Side::Buy).map(|o| o.unwrap_price());
-        let best_ask = self.find_bbo(Side::Sell).map(|o| o.unwrap_price());
+        let (best_bid, best_ask) = if dry_run {
+            self.projected_best_prices(&order, &matches, open_qty_lots)
+        } else {
+            (
+                self.best_price_lots(Side::Buy),
+                self.best_price_lots(Side::Sell),
+            )
+        };
 
-        PlaceOrderResult {
+        let result = PlaceOrderResult {
             id: order_id,
             fill_qty_lots,
             open_qty_lots,
-            quote_amount_lots: order
-                .available_quote_lots
-                .unwrap_or_default()
-                .checked_sub(unused_quote_lots.unwrap_or_default())
-                .unwrap_or_default(),
+            quote_amount_lots: _expect!(
+                quote_spent_lots(order.available_quote_lots, unused_quote_lots),
+                "unused quote exceeds available quote"
+            ),
             outcome,
             matches,
             price_rank,
             best_bid,
             best_ask,
+            client_id: order.client_id,
+            budget_exhausted: budget_exhausted && unfilled_qty_lots > 0,
+        };
+
+        #[cfg(any(test, feature = "integrity_checks"))]
+        if let Err(e) = check_fill_totals(&order, &result) {
+            near_sdk::env::panic_str(e);
+        }
+
+        Ok(result)
+    }
+
+    /// Best bid and ask that executing the order would leave, without
+    /// modifying the book. Used for dry runs.
+    fn projected_best_prices(
+        &self,
+        order: &NewOrder,
+        matches: &[Match],
+        open_qty_lots: LotBalance,
+    ) -> (Option<LotBalance>, Option<LotBalance>) {
+        let maker_side = match order.side {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+        // makers are matched in book order and only the last one can be
+        // partially filled, so the removed makers are a prefix of the side
+        let removed = matches
+            .iter()
+            .filter(|m| m.maker_order_removed == Some(true))
+            .count();
+        let maker_best = self
+            .side(maker_side)
+            .iter()
+            .nth(removed)
+            .map(|o| o.unwrap_price());
+
+        let current = self.best_price_lots(order.side);
+        let taker_best = match (current, order.limit_price_lots) {
+            (_, Some(price)) if open_qty_lots > 0 => Some(match (order.side, current) {
+                (_, None) => price,
+                (Side::Buy, Some(best)) => best.max(price),
+                (Side::Sell, Some(best)) => best.min(price),
+            }),
+            _ => current,
+        };
+
+        match order.side {
+            Side::Buy => (taker_best, maker_best),
+            Side::Sell => (maker_best, taker_best),
+        }
+    }
+
+    /// Cap a sell's quantity at its `notional_quote_lots`, if any.
+    fn apply_notional(&self, order: &mut NewOrder) {
+        let notional = match (order.side, order.notional_quote_lots) {
+            (Side::Sell, Some(notional)) => notional,
+            _ => return,
+        };
+        let calculator = OrderbookCalculator {
+            base_lot_size: order.base_lot_size,
+            quote_lot_size: order.quote_lot_size,
+            base_denomination: order.base_denomination,
+        };
+        let qty_lots = match order
+            .limit_price_lots
+            .or_else(|| self.best_price_lots(Side::Buy))
+        {
+            Some(price_lots) if price_lots > 0 => calculator.get_base_purchasable(
+                conv::lots_to_balance(notional) * calculator.quote_lot_size,
+                price_lots,
+            ),
+            _ => 0,
+        };
+        order.max_qty_lots = order.max_qty_lots.min(qty_lots);
+    }
+
+    /// Return true if posting the order would add a price level to a side
+    /// already at the order's `max_price_levels`.
+    fn exceeds_price_levels(&self, order: &NewOrder) -> bool {
+        match (order.max_price_levels, order.limit_price_lots) {
+            (Some(max_levels), Some(price_lots)) => {
+                let l2 = self.side(order.side);
+                !l2.has_price_level(price_lots) && l2.unique_prices_count() >= max_levels
+            }
+            _ => false,
         }
     }
 
     /// Match orders. The result can be used to alter the orderbook, settle
     /// balance changes, etc.
-    fn match_order(&self, user_id: &AccountId, order: &NewOrder) -> MatchOrderResult {
+    pub(crate) fn match_order(
+        &self,
+        user_id: &AccountId,
+        order: &NewOrder,
+    ) -> Result<MatchOrderResult, PlaceOrderError> {
         let calculator = OrderbookCalculator {
             base_lot_size: order.base_lot_size,
             quote_lot_size: order.quote_lot_size,
@@ -403,13 +1166,24 @@ impl<T: L2> Orderbook<T> {
         let mut unfilled_qty_lots = order.max_qty_lots;
         let mut unused_quote = order
             .available_quote_lots
-            .map(|l| calculator.quote_lot_size as u128 * l as u128);
+            .map(|l| calculator.quote_lot_size * l as u128);
 
         let check_if_crossed: fn(LotBalance, LotBalance) -> bool = match order.side {
             Side::Buy => |p1, p2| p1 <= p2,
             Side::Sell => |p1, p2| p1 >= p2,
         };
 
+        let exceeds_impact: fn(LotBalance, LotBalance, u16) -> bool = match order.side {
+            Side::Buy => |first, p, bps| p as u128 > BN!(first).add_bps(bps).as_u128(),
+            Side::Sell => |first, p, bps| {
+                (p as u128) < BN!(first).sub_bps(bps.min(BPS_DIVISOR as u16)).as_u128()
+            },
+        };
+        let mut first_fill_price_lots: Option<LotBalance> = None;
+        let mut hit_match_cap = false;
+        let mut hit_impact_band = false;
+        let mut budget_exhausted = false;
+
         let mut matches: Vec<Match> = vec![];
         let resting_orders = match order.side {
             Side::Buy => self.asks.iter(),
@@ -421,6 +1195,13 @@ impl<T: L2> Orderbook<T> {
 
             let crossed = order.limit_price_lots.is_none()
                 || check_if_crossed(trade_price_lots, order.limit_price_lots.unwrap());
+            debug_log!(
+                "match: maker {} ({} lots) at {}, crossed: {}",
+                best_match.sequence_number,
+                best_match.open_qty_lots,
+                trade_price_lots,
+                crossed
+            );
             if !crossed {
                 break;
             }
@@ -429,19 +1210,35 @@ impl<T: L2> Orderbook<T> {
                 break;
             }
 
+            if matches!(order.max_matches, Some(max) if matches.len() >= max) {
+                hit_match_cap = true;
+                break;
+            }
+
+            if let (Some(first), Some(bps)) = (first_fill_price_lots, order.max_impact_bps) {
+                if exceeds_impact(first, trade_price_lots, bps) {
+                    hit_impact_band = true;
+                    break;
+                }
+            }
+
             if best_match.owner_id == *user_id {
-                near_sdk::env::panic_str(errors::SELF_TRADE)
+                return Err(PlaceOrderError::SelfTrade);
             }
 
             let trade_qty_lots = match unused_quote {
                 // buying
                 Some(remaining_quote) => {
-                    let max_based_on_remaining_quote =
-                        calculator.get_base_purchasable(remaining_quote, trade_price_lots);
-                    best_match
-                        .open_qty_lots
-                        .min(unfilled_qty_lots)
-                        .min(max_based_on_remaining_quote)
+                    let max_based_on_remaining_quote = calculator.tradeable_base_lots(
+                        Side::Buy,
+                        remaining_quote,
+                        trade_price_lots,
+                    );
+                    let available_qty_lots = best_match.open_qty_lots.min(unfilled_qty_lots);
+                    if max_based_on_remaining_quote < available_qty_lots {
+                        budget_exhausted = true;
+                    }
+                    available_qty_lots.min(max_based_on_remaining_quote)
                 }
                 // selling
                 _ => best_match.open_qty_lots.min(unfilled_qty_lots),
@@ -459,32 +1256,40 @@ impl<T: L2> Orderbook<T> {
                 unused_quote = Some(unused_quote.unwrap() - native_quote_paid);
             }
 
+            debug_log!(
+                "match: traded {} lots at {}, {} lots unfilled, quote remaining: {:?}",
+                trade_qty_lots,
+                trade_price_lots,
+                unfilled_qty_lots,
+                unused_quote
+            );
+            first_fill_price_lots.get_or_insert(trade_price_lots);
             matches.push(Match {
                 maker_order_id: best_match.id(),
                 maker_user_id: best_match.owner_id.clone(),
                 fill_qty_lots: trade_qty_lots,
                 fill_price_lots: trade_price_lots,
                 native_quote_paid,
+                native_base_filled: trade_qty_lots as u128 * calculator.base_lot_size,
                 maker_order_removed: None,
                 maker_order_price_rank: best_match.unwrap_price_rank(),
             });
         }
 
-        MatchOrderResult {
+        Ok(MatchOrderResult {
             unfilled_qty_lots,
-            // TODO: change this to use full native size
-            unused_quote_lots: unused_quote.map(|n| (n / calculator.quote_lot_size) as u64),
+            unused_quote_native: unused_quote,
             matches,
-        }
+            hit_match_cap,
+            hit_impact_band,
+            budget_exhausted,
+        })
     }
 
     /// Fetch an [OpenLimitOrder], if it exists
     pub fn get_order(&self, order_id: OrderId) -> Option<OpenLimitOrder> {
         let (side, price_lots, seq) = get_order_id_parts(order_id);
-        let order = match side {
-            Side::Buy => self.bids.get_order(price_lots, seq),
-            Side::Sell => self.asks.get_order(price_lots, seq),
-        };
+        let order = self.side(side).get_order(price_lots, seq);
         if let Some(mut order) = order {
             order.side = side.into();
             Some(order)
@@ -496,12 +1301,13 @@ impl<T: L2> Orderbook<T> {
     /// Remove an order from the book
     pub fn remove_order(&mut self, order_id: OrderId) -> Option<OpenLimitOrder> {
         let (side, price_lots, seq) = get_order_id_parts(order_id);
-        let order = match side {
-            Side::Buy => self.bids.delete_order(price_lots, seq),
-            Side::Sell => self.asks.delete_order(price_lots, seq),
-        };
+        let order = self.side_mut(side).delete_order(price_lots, seq);
         if let Some(mut order) = order {
             order.side = side.into();
+            // removing an order can't cross the book; if it is, the book was
+            // already corrupted
+            #[cfg(any(test, feature = "integrity_checks"))]
+            _assert!(!self.is_crossed(), "orderbook crossed after removal");
             Some(order)
         } else {
             None
@@ -510,8 +1316,8 @@ impl<T: L2> Orderbook<T> {
 
     pub fn cancel_order(&mut self, order_id: OrderId) -> Option<CancelOrderResult> {
         if let Some(order) = self.remove_order(order_id) {
-            let best_bid = self.find_bbo(Side::Buy).map(|o| o.unwrap_price());
-            let best_ask = self.find_bbo(Side::Sell).map(|o| o.unwrap_price());
+            let best_bid = self.best_price_lots(Side::Buy);
+            let best_ask = self.best_price_lots(Side::Sell);
             Some(CancelOrderResult {
                 order,
                 best_bid,
@@ -522,12 +1328,74 @@ impl<T: L2> Orderbook<T> {
         }
     }
 
+    /// Remove an order, panicking if it doesn't exist. For callers that treat
+    /// cancelling a missing order as a bug rather than a no-op.
+    pub fn cancel_order_strict(&mut self, order_id: OrderId) -> OpenLimitOrder {
+        _expect!(self.remove_order(order_id), errors::ORDER_NOT_FOUND)
+    }
+
+    /// Remove every order on one side of the book, eg, to cancel all asks
+    /// during a halt. Returns the removed orders in priority order (best price
+    /// first, then oldest first), for refunding.
+    pub fn clear_side(&mut self, side: Side) -> Vec<OpenLimitOrder> {
+        self.remove_where(side, |_| true)
+    }
+
+    /// All resting orders: asks by ascending price, then bids by descending
+    /// price, with orders at the same price by ascending sequence number. The
+    /// order doesn't depend on the [L2] implementation.
+    pub fn all_orders(&self) -> Vec<OpenLimitOrder> {
+        let mut asks: Vec<OpenLimitOrder> = self.asks.iter().collect();
+        sort_by_priority(Side::Sell, &mut asks);
+        let mut bids: Vec<OpenLimitOrder> = self.bids.iter().collect();
+        sort_by_priority(Side::Buy, &mut bids);
+        asks.extend(bids);
+        asks
+    }
+
+    /// Remove all orders from the book. Returns them in the same order as
+    /// [all_orders](Orderbook::all_orders).
+    pub fn drain_all(&mut self) -> Vec<OpenLimitOrder> {
+        let mut removed = self.clear_side(Side::Sell);
+        removed.extend(self.clear_side(Side::Buy));
+        removed
+    }
+
+    /// Remove all of an account's orders. Returns them in the same order as
+    /// [all_orders](Orderbook::all_orders).
+    pub fn cancel_all_for_owner(&mut self, owner_id: &AccountId) -> Vec<OpenLimitOrder> {
+        let mut removed = self.remove_where(Side::Sell, |o| o.owner_id == *owner_id);
+        removed.extend(self.remove_where(Side::Buy, |o| o.owner_id == *owner_id));
+        removed
+    }
+
+    /// Remove orders on one side matching a predicate, returning them in
+    /// priority order.
+    fn remove_where(
+        &mut self,
+        side: Side,
+        predicate: impl Fn(&OpenLimitOrder) -> bool,
+    ) -> Vec<OpenLimitOrder> {
+        let l2 = self.side_mut(side);
+        let keys: Vec<(LotBalance, SequenceNumber)> = l2
+            .iter()
+            .filter(|o| predicate(o))
+            .map(|o| (o.unwrap_price(), o.sequence_number))
+            .collect();
+        let mut removed: Vec<OpenLimitOrder> = keys
+            .into_iter()
+            .filter_map(|(price_lots, seq)| l2.delete_order(price_lots, seq))
+            .collect();
+        sort_by_priority(side, &mut removed);
+        removed
+    }
+
     /// Cancel orders. Note that, because this is an atomic operation, best bid/ask prices are
     /// computed *once* before the first order is removed.
     pub fn cancel_orders(&mut self, order_ids: Vec<OrderId>) -> Vec<CancelOrderResult> {
         let mut deleted: Vec<CancelOrderResult> = vec![];
-        let best_bid = self.find_bbo(Side::Buy).map(|o| o.unwrap_price());
-        let best_ask = self.find_bbo(Side::Sell).map(|o| o.unwrap_price());
+        let best_bid = self.best_price_lots(Side::Buy);
+        let best_ask = self.best_price_lots(Side::Sell);
         for order_id in order_ids.into_iter() {
             if let Some(order) = self.remove_order(order_id) {
                 deleted.push(CancelOrderResult {
@@ -541,4 +1409,100 @@ impl<T: L2> Orderbook<T> {
         }
         deleted
     }
+
+    /// Same as [cancel_orders](Orderbook::cancel_orders), also returning the
+    /// total [refund](OpenLimitOrder::refund_on_cancel) of the cancelled
+    /// orders in base and quote.
+    pub fn cancel_orders_with_tvl(
+        &mut self,
+        order_ids: Vec<OrderId>,
+        calc: &OrderbookCalculator,
+    ) -> (Vec<OpenLimitOrder>, Tvl) {
+        let cancelled: Vec<OpenLimitOrder> = self
+            .cancel_orders(order_ids)
+            .into_iter()
+            .map(|res| res.order)
+            .collect();
+        let tvl = cancelled
+            .iter()
+            .map(|o| {
+                let refund = o.refund_on_cancel(calc);
+                match o.unwrap_side() {
+                    Side::Buy => Tvl {
+                        base_locked: 0,
+                        quote_locked: refund,
+                    },
+                    Side::Sell => Tvl {
+                        base_locked: refund,
+                        quote_locked: 0,
+                    },
+                }
+            })
+            .sum();
+        (cancelled, tvl)
+    }
+}
+
+/// Quote lots spent by an order given the quote it started with and the quote
+/// left over after matching.
+///
+/// Unused quote exceeding available quote means the matching engine's
+/// accounting is wrong. This returns [None] in that case when integrity checks
+/// are enabled, and otherwise saturates to 0.
+pub(crate) fn quote_spent_lots(
+    available_quote_lots: Option<LotBalance>,
+    unused_quote_lots: Option<LotBalance>,
+) -> Option<LotBalance> {
+    let available = available_quote_lots.unwrap_or_default();
+    let unused = unused_quote_lots.unwrap_or_default();
+    if cfg!(any(test, feature = "integrity_checks")) {
+        available.checked_sub(unused)
+    } else {
+        Some(available.saturating_sub(unused))
+    }
+}
+
+/// Check that the totals tracked while matching agree with the totals derived
+/// from the individual matches:
+///
+/// - `Σ native_base_filled == fill_qty_lots × base_lot_size`
+/// - for a buy with `available_quote_lots`,
+///   `quote_amount_lots == ⌈Σ native_quote_paid / quote_lot_size⌉`, since the
+///   unused quote is rounded down to lots before being subtracted from the
+///   budget. Otherwise `quote_amount_lots == 0`.
+#[cfg(any(test, feature = "integrity_checks"))]
+// `is_multiple_of` and `div_ceil` aren't available on older toolchains
+#[allow(clippy::manual_is_multiple_of)]
+pub(crate) fn check_fill_totals(
+    order: &NewOrder,
+    result: &PlaceOrderResult,
+) -> Result<(), &'static str> {
+    let base_filled: Balance = result.matches.iter().map(|m| m.native_base_filled).sum();
+    if base_filled != result.fill_qty_lots as Balance * order.base_lot_size {
+        return Err("matched base doesn't match fill quantity");
+    }
+
+    let quote_paid: Balance = result.matches.iter().map(|m| m.native_quote_paid).sum();
+    let expected_quote_lots = match (order.side, order.available_quote_lots) {
+        // rounded up, like the quote amount
+        (Side::Buy, Some(_)) => {
+            let lot_size = order.quote_lot_size;
+            quote_paid / lot_size + (quote_paid % lot_size != 0) as Balance
+        }
+        _ => 0,
+    };
+    if result.quote_amount_lots as Balance != expected_quote_lots {
+        return Err("matched quote doesn't match quote amount");
+    }
+    Ok(())
+}
+
+/// Sort orders from one side of the book best price first, then oldest first.
+fn sort_by_priority(side: Side, orders: &mut [OpenLimitOrder]) {
+    match side {
+        Side::Buy => {
+            orders.sort_by_key(|o| (std::cmp::Reverse(o.unwrap_price()), o.sequence_number))
+        }
+        Side::Sell => orders.sort_by_key(|o| (o.unwrap_price(), o.sequence_number)),
+    }
 }
@@ -1,9 +1,12 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::Balance;
-use tonic_sdk_dex_types::{LotBalance, U256};
+use tonic_sdk_dex_types::{LotBalance, Side, U256};
+use tonic_sdk_macros::*;
 
 use crate::*;
 
 /// Struct for doing math in the orderbook.
+#[derive(BorshSerialize, BorshDeserialize)]
 pub struct OrderbookCalculator {
     pub base_lot_size: Balance,
     pub quote_lot_size: Balance,
@@ -30,6 +33,13 @@ impl OrderbookCalculator {
         )
     }
 
+    /// Get the native quote a seller receives for selling `base_lots` at
+    /// `price_lots`. Same as [get_bid_quote_value](Self::get_bid_quote_value),
+    /// named for the sell side.
+    pub fn quote_received_on_sell(&self, base_lots: LotBalance, price_lots: LotBalance) -> Balance {
+        self.get_bid_quote_value(base_lots, price_lots)
+    }
+
     /// Get quantity of base that a given amount of quote is worth in terms of base lots
     pub fn get_base_purchasable(&self, quote_amount: Balance, price: LotBalance) -> LotBalance {
         get_base_purchasable(
@@ -40,24 +50,82 @@ impl OrderbookCalculator {
             self.base_denomination,
         )
     }
-}
 
-/// Get the value of a bid in terms of native quote token.
-///
-/// Conceptually, this is price * quantity.
-pub fn get_bid_quote_value(
-    quantity: LotBalance,
-    price: LotBalance,
-    base_lot_size: Balance,
-    quote_lot_size: Balance,
-    base_denomination: Balance,
-) -> Balance {
-    BN!(quantity)
-        .mul(base_lot_size)
-        .mul(price as u128)
-        .mul(quote_lot_size)
-        .div(base_denomination)
-        .as_u128()
+    /// Value of `base_lots` in native quote at `price_lots`, rounded down.
+    /// Same as [get_bid_quote_value](Self::get_bid_quote_value).
+    pub fn base_to_quote(&self, base_lots: LotBalance, price_lots: LotBalance) -> Balance {
+        self.get_bid_quote_value(base_lots, price_lots)
+    }
+
+    /// Number of whole base lots `quote_native` buys at `price_lots`, rounded
+    /// down. Same as [get_base_purchasable](Self::get_base_purchasable).
+    pub fn quote_to_base(&self, quote_native: Balance, price_lots: LotBalance) -> LotBalance {
+        self.get_base_purchasable(quote_native, price_lots)
+    }
+
+    /// Get the number of base lots that can be traded at a price given the
+    /// resource constraining the order: the native quote budget for a buy, or
+    /// the native base quantity for a sell. Saturates at the max lot balance.
+    pub fn tradeable_base_lots(
+        &self,
+        side: Side,
+        resource: Balance,
+        price: LotBalance,
+    ) -> LotBalance {
+        match side {
+            Side::Buy => self.get_base_purchasable(resource, price),
+            Side::Sell => conv::native_to_lots_checked(resource, self.base_lot_size)
+                .unwrap_or(LotBalance::MAX),
+        }
+    }
+
+    /// Smallest non-zero native base amount a fill can move: one base lot.
+    pub fn base_dust_threshold(&self) -> Balance {
+        self.base_lot_size
+    }
+
+    /// Smallest non-zero native quote amount a fill can move: the quote value
+    /// of one base lot at a price of one quote lot. Never less than 1.
+    pub fn quote_dust_threshold(&self) -> Balance {
+        self.get_bid_quote_value(1, 1).max(1)
+    }
+
+    /// Return true if a native base amount is too small to be moved by any
+    /// fill, ie, it's below the
+    /// [base_dust_threshold](Self::base_dust_threshold). Zero is dust.
+    pub fn is_base_dust(&self, native: Balance) -> bool {
+        native < self.base_dust_threshold()
+    }
+
+    /// Return true if a native quote amount is too small to be moved by any
+    /// fill, ie, it's below the
+    /// [quote_dust_threshold](Self::quote_dust_threshold). Zero is dust.
+    pub fn is_quote_dust(&self, native: Balance) -> bool {
+        native < self.quote_dust_threshold()
+    }
+
+    /// Snap a price to a multiple of `tick_lots`, rounding toward the passive
+    /// side of the book: down for bids, up for asks. Prices already on the
+    /// grid are unchanged. The result is never below `tick_lots`, since a
+    /// price of 0 isn't valid.
+    pub fn round_to_tick(
+        &self,
+        price_lots: LotBalance,
+        tick_lots: LotBalance,
+        side: Side,
+    ) -> LotBalance {
+        _assert!(tick_lots > 0, "tick size is 0");
+        let remainder = price_lots % tick_lots;
+        let rounded = match side {
+            _ if remainder == 0 => price_lots,
+            Side::Buy => price_lots - remainder,
+            Side::Sell => _expect!(
+                price_lots.checked_add(tick_lots - remainder),
+                "price overflow"
+            ),
+        };
+        rounded.max(tick_lots)
+    }
 }
 
 /// Get quantity of base that a given amount of quote is worth in terms of base lots
@@ -70,10 +138,215 @@ pub fn get_base_purchasable(
     base_lot_size: Balance,
     base_denomination: Balance,
 ) -> LotBalance {
-    BN!(quote_amount)
+    let lots = BN!(quote_amount)
         .mul(base_denomination)
-        .div(quote_lot_size as u128)
+        .div(quote_lot_size)
         .div(price as u128)
         .div(base_lot_size)
-        .as_u64()
+        .as_u64();
+    if cfg!(feature = "debug_log") {
+        // the chained divisions discard a remainder iff the single division
+        // by their product would
+        let divisor = U256::from(quote_lot_size)
+            .saturating_mul(U256::from(price))
+            .saturating_mul(U256::from(base_lot_size));
+        if !(U256::from(quote_amount) * U256::from(base_denomination) % divisor).is_zero() {
+            debug_log!(
+                "precision loss: {} native quote at price {} buys a fractional lot, rounded down to {}",
+                quote_amount,
+                price,
+                lots
+            );
+        }
+    }
+    lots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculator() -> OrderbookCalculator {
+        OrderbookCalculator {
+            base_lot_size: 1_000,
+            quote_lot_size: 10,
+            base_denomination: 1_000_000,
+        }
+    }
+
+    #[test]
+    fn quote_received_on_sell() {
+        let calc = calculator();
+        for (lots, price) in [(0, 500), (1, 500), (7, 333), (1_000, 1)] {
+            assert_eq!(
+                calc.quote_received_on_sell(lots, price),
+                calc.get_bid_quote_value(lots, price)
+            );
+        }
+        // 7 lots of 1000 at 500 quote lots of 10 per 1e6 base
+        assert_eq!(calc.quote_received_on_sell(7, 500), 35);
+    }
+
+    #[test]
+    fn tradeable_base_lots_buy() {
+        let calc = calculator();
+        // one base lot at a price of 500 quote lots per whole base
+        let one_lot_value = calc.get_bid_quote_value(1, 500);
+        assert_eq!(
+            calc.tradeable_base_lots(Side::Buy, one_lot_value * 7, 500),
+            7
+        );
+        assert_eq!(
+            calc.tradeable_base_lots(Side::Buy, one_lot_value * 7, 500),
+            calc.get_base_purchasable(one_lot_value * 7, 500)
+        );
+        assert_eq!(
+            calc.tradeable_base_lots(Side::Buy, one_lot_value - 1, 500),
+            0
+        );
+    }
+
+    #[test]
+    fn round_to_tick() {
+        let calc = calculator();
+
+        // on the grid
+        assert_eq!(calc.round_to_tick(500, 5, Side::Buy), 500);
+        assert_eq!(calc.round_to_tick(500, 5, Side::Sell), 500);
+
+        // off the grid
+        assert_eq!(calc.round_to_tick(503, 5, Side::Buy), 500);
+        assert_eq!(calc.round_to_tick(503, 5, Side::Sell), 505);
+        assert_eq!(calc.round_to_tick(3, 5, Side::Sell), 5);
+
+        // below one tick, a bid clamps up to the tick instead of 0
+        assert_eq!(calc.round_to_tick(3, 5, Side::Buy), 5);
+        assert_eq!(calc.round_to_tick(0, 5, Side::Buy), 5);
+        assert_eq!(calc.round_to_tick(0, 5, Side::Sell), 5);
+
+        // tick of 1 is a no-op
+        assert_eq!(calc.round_to_tick(503, 1, Side::Buy), 503);
+        assert_eq!(calc.round_to_tick(503, 1, Side::Sell), 503);
+    }
+
+    #[test]
+    fn tradeable_base_lots_sell() {
+        let calc = calculator();
+        // price doesn't matter when selling
+        assert_eq!(calc.tradeable_base_lots(Side::Sell, 7_000, 500), 7);
+        assert_eq!(calc.tradeable_base_lots(Side::Sell, 7_999, 1), 7);
+        assert_eq!(calc.tradeable_base_lots(Side::Sell, 999, 500), 0);
+        assert_eq!(
+            calc.tradeable_base_lots(Side::Sell, u128::MAX, 500),
+            LotBalance::MAX
+        );
+    }
+
+    #[test]
+    fn base_quote_conversion() {
+        let calc = calculator();
+        // 1 lot of 1000 at 500 quote lots of 10 per 1e6 base is 5 native
+        assert_eq!(calc.base_to_quote(1, 500), 5);
+        assert_eq!(calc.quote_to_base(5, 500), 1);
+
+        // exact amounts round trip
+        for (lots, price) in [(0, 500), (7, 500), (1_000, 1), (3, 1_000_000)] {
+            let quote = calc.base_to_quote(lots, price);
+            assert_eq!(calc.quote_to_base(quote, price), lots);
+        }
+
+        // quote that isn't worth a whole lot truncates to the lot below
+        assert_eq!(calc.quote_to_base(9, 500), 1);
+        assert_eq!(calc.quote_to_base(4, 500), 0);
+
+        // 1 lot at 333 is worth 3.33 native, truncated to 3, which no longer
+        // buys the lot back
+        assert_eq!(calc.base_to_quote(1, 333), 3);
+        assert_eq!(calc.quote_to_base(3, 333), 0);
+    }
+
+    #[test]
+    fn dust_threshold() {
+        let calc = calculator();
+        assert_eq!(calc.base_dust_threshold(), 1_000);
+        assert!(calc.is_base_dust(999));
+        assert!(!calc.is_base_dust(1_000));
+        // one base lot at one quote lot is worth less than 1 native quote
+        assert_eq!(calc.quote_dust_threshold(), 1);
+        assert!(calc.is_quote_dust(0));
+        assert!(!calc.is_quote_dust(1));
+
+        // each threshold is in its own token's units
+        let calc = OrderbookCalculator {
+            base_lot_size: 100,
+            quote_lot_size: 1_000,
+            base_denomination: 10,
+        };
+        assert_eq!(calc.base_dust_threshold(), 100);
+        assert!(calc.is_base_dust(99));
+        assert!(!calc.is_base_dust(100));
+        assert_eq!(calc.quote_dust_threshold(), 10_000);
+        assert!(calc.is_quote_dust(9_999));
+        assert!(!calc.is_quote_dust(10_000));
+    }
+
+    proptest::proptest! {
+        /// The methods take their lot sizes from the calculator's fields while
+        /// the free functions take them as arguments in a different order, so
+        /// check that nothing got swapped.
+        #[test]
+        fn methods_match_free_functions(
+            base_lot_decimals in 0..12u32,
+            quote_lot_decimals in 0..6u32,
+            base_decimals in 0..24u32,
+            quantity in 1..1_000_000_000u64,
+            price in 1..1_000_000_000u64,
+            quote_amount in 0..1_000_000_000_000_000_000_000_000u128,
+        ) {
+            // reset the mocked chain so precision loss logs from previous cases
+            // don't hit the log limit
+            #[cfg(feature = "debug_log")]
+            near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new().build());
+            let base_lot_size = 10u128.pow(base_lot_decimals);
+            let quote_lot_size = 10u128.pow(quote_lot_decimals);
+            let base_denomination = 10u128.pow(base_decimals);
+            let calc = OrderbookCalculator {
+                base_lot_size,
+                quote_lot_size,
+                base_denomination,
+            };
+
+            proptest::prop_assert_eq!(
+                calc.get_bid_quote_value(quantity, price),
+                get_bid_quote_value(quantity, price, base_lot_size, quote_lot_size, base_denomination)
+            );
+
+            // skip inputs where the result doesn't fit in a lot balance
+            let purchasable = BN!(quote_amount)
+                .mul(base_denomination)
+                .div(quote_lot_size)
+                .div(price as u128)
+                .div(base_lot_size);
+            proptest::prop_assume!(purchasable.0 <= U256::from(u64::MAX));
+            proptest::prop_assert_eq!(
+                calc.get_base_purchasable(quote_amount, price),
+                get_base_purchasable(quote_amount, price, quote_lot_size, base_lot_size, base_denomination)
+            );
+        }
+    }
+
+    #[cfg(feature = "debug_log")]
+    #[test]
+    fn get_base_purchasable_logs_precision_loss() {
+        let calc = calculator();
+        // 5000 native quote at 500 quote lots per whole base is exactly 1
+        // base, ie, 1000 lots
+        assert_eq!(calc.get_base_purchasable(5_000, 500), 1_000);
+        assert!(near_sdk::test_utils::get_logs().is_empty());
+
+        assert_eq!(calc.get_base_purchasable(5_004, 500), 1_000);
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].contains("precision loss"));
+    }
 }
@@ -0,0 +1,80 @@
+/// Conversions between lot balances (u64) and native balances (u128).
+use near_sdk::Balance;
+use tonic_sdk_dex_types::LotBalance;
+use tonic_sdk_macros::*;
+
+/// Widen a lot balance. Always safe.
+pub fn lots_to_balance(lots: LotBalance) -> Balance {
+    lots as Balance
+}
+
+/// Narrow a balance to a lot balance. Returns [None] if the value doesn't fit
+/// in a [LotBalance] instead of silently truncating.
+pub fn balance_to_lots_checked(balance: Balance) -> Option<LotBalance> {
+    if balance > LotBalance::MAX as Balance {
+        None
+    } else {
+        Some(balance as LotBalance)
+    }
+}
+
+/// Convert a native amount to lots, rounding down. Returns [None] if the
+/// result doesn't fit in a [LotBalance].
+///
+/// With the `debug_log` feature, logs when rounding discards part of the
+/// amount.
+// `is_multiple_of` isn't available on older toolchains
+#[allow(clippy::manual_is_multiple_of)]
+pub fn native_to_lots_checked(amount: Balance, lot_size: Balance) -> Option<LotBalance> {
+    let lots = balance_to_lots_checked(amount / lot_size);
+    if cfg!(feature = "debug_log") && amount % lot_size != 0 {
+        debug_log!(
+            "precision loss: {} native is not a multiple of lot size {}",
+            amount,
+            lot_size
+        );
+    }
+    lots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for lots in [0, 1, 12345, u64::MAX - 1, u64::MAX] {
+            assert_eq!(balance_to_lots_checked(lots_to_balance(lots)), Some(lots));
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(
+            balance_to_lots_checked(u64::MAX as u128),
+            Some(u64::MAX),
+            "max lot balance should fit"
+        );
+        assert_eq!(balance_to_lots_checked(u64::MAX as u128 + 1), None);
+        assert_eq!(balance_to_lots_checked(u128::MAX), None);
+    }
+
+    #[test]
+    fn native_to_lots_rounds_down() {
+        assert_eq!(native_to_lots_checked(1_999, 1_000), Some(1));
+        assert_eq!(native_to_lots_checked(2_000, 1_000), Some(2));
+        assert_eq!(native_to_lots_checked(u128::MAX, 1), None);
+    }
+
+    #[cfg(feature = "debug_log")]
+    #[test]
+    fn native_to_lots_logs_precision_loss() {
+        native_to_lots_checked(2_000, 1_000);
+        assert!(near_sdk::test_utils::get_logs().is_empty());
+
+        native_to_lots_checked(1_999, 1_000);
+        let logs = near_sdk::test_utils::get_logs();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].contains("precision loss"));
+    }
+}
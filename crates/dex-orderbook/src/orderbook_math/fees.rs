@@ -0,0 +1,48 @@
+use near_sdk::Balance;
+use tonic_sdk_macros::*;
+
+use crate::*;
+
+/// Get the portion of a taker fee paid to the referrer.
+///
+/// Rounds down, so any remainder stays with the protocol and the rebate never
+/// exceeds the fee. `referral_bps` is out of [BPS_DIVISOR] and must not
+/// exceed it.
+pub fn referral_rebate(taker_fee: Balance, referral_bps: u16) -> Balance {
+    _assert!(
+        referral_bps as u128 <= BPS_DIVISOR,
+        "referral bps exceeds 100%"
+    );
+    BN!(taker_fee)
+        .mul(referral_bps as u128)
+        .div(BPS_DIVISOR)
+        .as_u128()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebate() {
+        assert_eq!(referral_rebate(1_000_000, 0), 0);
+        assert_eq!(referral_rebate(1_000_000, 1), 100);
+        assert_eq!(referral_rebate(1_000_000, 2_500), 250_000);
+        assert_eq!(referral_rebate(1_000_000, 5_000), 500_000);
+        assert_eq!(referral_rebate(1_000_000, 10_000), 1_000_000);
+    }
+
+    #[test]
+    fn rebate_rounds_down() {
+        // 3 * 0.5 = 1.5
+        assert_eq!(referral_rebate(3, 5_000), 1);
+        assert_eq!(referral_rebate(9_999, 1), 0);
+        assert_eq!(referral_rebate(0, 10_000), 0);
+    }
+
+    #[test]
+    fn rebate_no_overflow() {
+        assert_eq!(referral_rebate(u128::MAX, 10_000), u128::MAX);
+        assert_eq!(referral_rebate(u128::MAX, 5_000), u128::MAX / 2);
+    }
+}
@@ -1,5 +1,9 @@
-mod bn;
 mod calculator;
+pub mod conv;
+mod fees;
 
-pub use bn::*;
 pub use calculator::*;
+pub use fees::*;
+
+// moved to dex-types so they compile without near_sdk
+pub use tonic_sdk_dex_types::{get_bid_quote_value, BN, BPS_DIVISOR};
@@ -0,0 +1,306 @@
+/// User-facing order parameters and their conversion to [NewOrder].
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use tonic_sdk_dex_types::{LotBalance, OrderType, SequenceNumber, Side};
+use tonic_sdk_macros::*;
+
+use crate::*;
+
+/// Order parameters in native token units, as accepted from users. Converted
+/// to lots with [NewOrderParams::into_new_order] before being passed to the
+/// matching engine.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NewOrderParams {
+    /// Price of one whole base token in native quote. Required unless the
+    /// order is a market order.
+    pub limit_price: Option<U128>,
+    /// Maximum amount of native quote to spend. Only used for buys.
+    pub max_spend: Option<U128>,
+    /// Amount of native base to buy or sell.
+    pub quantity: U128,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub client_id: Option<ClientId>,
+    pub max_impact_bps: Option<u16>,
+}
+
+impl NewOrderParams {
+    /// Convert the limit price to quote lots, rounding down. Returns
+    /// [PlaceOrderError::PriceOverflow] if the price doesn't fit in a
+    /// [LotBalance].
+    pub fn limit_price_lots(
+        &self,
+        calc: &OrderbookCalculator,
+    ) -> Result<Option<LotBalance>, PlaceOrderError> {
+        self.limit_price
+            .map(|p| {
+                conv::native_to_lots_checked(p.0, calc.quote_lot_size)
+                    .ok_or(PlaceOrderError::PriceOverflow)
+            })
+            .transpose()
+    }
+
+    /// Convert native amounts to lots. Amounts that aren't a multiple of the
+    /// lot size are rounded down.
+    ///
+    /// Panics with [errors::PRICE_OVERFLOW] if the limit price doesn't fit in
+    /// a [LotBalance].
+    pub fn into_new_order(
+        self,
+        calc: &OrderbookCalculator,
+        sequence_number: SequenceNumber,
+    ) -> NewOrder {
+        let to_lots = |amount: U128, lot_size: u128| {
+            _expect!(
+                conv::native_to_lots_checked(amount.0, lot_size),
+                "lot balance overflow"
+            )
+        };
+        let limit_price_lots = self
+            .limit_price_lots(calc)
+            .unwrap_or_else(|e| near_sdk::env::panic_str(e.as_str()));
+        let available_quote_lots = match self.side {
+            Side::Buy => self.max_spend.map(|s| to_lots(s, calc.quote_lot_size)),
+            Side::Sell => None,
+        };
+
+        NewOrder {
+            sequence_number,
+            limit_price_lots,
+            available_quote_lots,
+            max_qty_lots: to_lots(self.quantity, calc.base_lot_size),
+            max_impact_bps: self.max_impact_bps,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
+            side: self.side,
+            order_type: self.order_type,
+            base_denomination: calc.base_denomination,
+            quote_lot_size: calc.quote_lot_size,
+            base_lot_size: calc.base_lot_size,
+            client_id: self.client_id,
+        }
+    }
+}
+
+impl From<&NewOrder> for NewOrderParams {
+    fn from(order: &NewOrder) -> Self {
+        let max_spend = match order.side {
+            Side::Buy => order
+                .available_quote_lots
+                .map(|l| U128(conv::lots_to_balance(l) * order.quote_lot_size)),
+            Side::Sell => None,
+        };
+        Self {
+            limit_price: order
+                .limit_price_lots
+                .map(|l| U128(conv::lots_to_balance(l) * order.quote_lot_size)),
+            max_spend,
+            quantity: U128(conv::lots_to_balance(order.max_qty_lots) * order.base_lot_size),
+            side: order.side,
+            order_type: order.order_type,
+            client_id: order.client_id,
+            max_impact_bps: order.max_impact_bps,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculator() -> OrderbookCalculator {
+        OrderbookCalculator {
+            base_lot_size: 1_000,
+            quote_lot_size: 10,
+            base_denomination: 1_000_000,
+        }
+    }
+
+    fn limit_buy() -> NewOrderParams {
+        NewOrderParams {
+            limit_price: Some(U128(5_000)),
+            max_spend: Some(U128(25_000)),
+            quantity: U128(5_000_000),
+            side: Side::Buy,
+            order_type: OrderType::Limit,
+            client_id: Some(7),
+            max_impact_bps: None,
+        }
+    }
+
+    #[test]
+    fn into_new_order() {
+        let order = limit_buy().into_new_order(&calculator(), 3);
+        assert_eq!(order.sequence_number, 3);
+        assert_eq!(order.limit_price_lots, Some(500));
+        assert_eq!(order.available_quote_lots, Some(2_500));
+        assert_eq!(order.max_qty_lots, 5_000);
+        assert_eq!(order.client_id, Some(7));
+        assert_eq!(order.base_lot_size, 1_000);
+        assert_eq!(order.quote_lot_size, 10);
+        assert_eq!(order.base_denomination, 1_000_000);
+        order.assert_valid();
+    }
+
+    #[test]
+    fn round_trip() {
+        let calc = calculator();
+        let buy = limit_buy();
+        let order = buy.clone().into_new_order(&calc, 1);
+        assert_eq!(NewOrderParams::from(&order), buy);
+        assert_eq!(NewOrderParams::from(&order).into_new_order(&calc, 1), order);
+
+        let sell = NewOrderParams {
+            limit_price: Some(U128(5_010)),
+            max_spend: None,
+            quantity: U128(3_000),
+            side: Side::Sell,
+            order_type: OrderType::PostOnly,
+            client_id: None,
+            max_impact_bps: None,
+        };
+        let order = sell.clone().into_new_order(&calc, 2);
+        assert_eq!(order.available_quote_lots, None);
+        assert_eq!(NewOrderParams::from(&order), sell);
+    }
+
+    #[test]
+    fn rounds_down_to_lots() {
+        let params = NewOrderParams {
+            limit_price: Some(U128(5_009)),
+            quantity: U128(5_999),
+            ..limit_buy()
+        };
+        let order = params.into_new_order(&calculator(), 1);
+        assert_eq!(order.limit_price_lots, Some(500));
+        assert_eq!(order.max_qty_lots, 5);
+    }
+
+    #[test]
+    fn limit_price_overflow() {
+        let calc = calculator();
+        let max_price = u64::MAX as u128 * calc.quote_lot_size;
+        let params = NewOrderParams {
+            limit_price: Some(U128(max_price)),
+            ..limit_buy()
+        };
+        assert_eq!(params.limit_price_lots(&calc), Ok(Some(u64::MAX)));
+
+        let params = NewOrderParams {
+            limit_price: Some(U128(max_price + calc.quote_lot_size)),
+            ..limit_buy()
+        };
+        assert_eq!(
+            params.limit_price_lots(&calc),
+            Err(PlaceOrderError::PriceOverflow)
+        );
+
+        let params = NewOrderParams {
+            limit_price: None,
+            ..limit_buy()
+        };
+        assert_eq!(params.limit_price_lots(&calc), Ok(None));
+    }
+
+    #[test]
+    fn max_spend_ignored_for_sells() {
+        let params = NewOrderParams {
+            side: Side::Sell,
+            ..limit_buy()
+        };
+        let order = params.into_new_order(&calculator(), 1);
+        assert_eq!(order.available_quote_lots, None);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let json = r#"{"limit_price":"5000","max_spend":"25000","quantity":"5000000","side":"Buy","order_type":"Limit","client_id":7,"max_impact_bps":null}"#;
+        let params: NewOrderParams = near_sdk::serde_json::from_str(json).unwrap();
+        assert_eq!(params, limit_buy());
+        assert_eq!(near_sdk::serde_json::to_string(&params).unwrap(), json);
+    }
+
+    const NEAR_DENOMINATION: u128 = 1_000_000_000_000_000_000_000_000;
+    const USDC_DENOMINATION: u128 = 1_000_000;
+
+    /// NEAR/USDC market with 0.001 NEAR base lots and 0.001 USDC quote lots.
+    fn near_usdc_calculator() -> OrderbookCalculator {
+        OrderbookCalculator {
+            base_lot_size: NEAR_DENOMINATION / 1_000,
+            quote_lot_size: USDC_DENOMINATION / 1_000,
+            base_denomination: NEAR_DENOMINATION,
+        }
+    }
+
+    /// The market buy from the [OrderType::Market] docs: buy 10 NEAR, spending
+    /// at most 105 USDC.
+    fn doc_example_params() -> NewOrderParams {
+        NewOrderParams {
+            limit_price: None,
+            max_spend: Some(U128(105 * USDC_DENOMINATION)),
+            quantity: U128(10 * NEAR_DENOMINATION),
+            side: Side::Buy,
+            order_type: OrderType::Market,
+            client_id: None,
+            max_impact_bps: None,
+        }
+    }
+
+    fn resting_ask(calc: &OrderbookCalculator, sequence_number: u64, price: u128) -> NewOrder {
+        NewOrderParams {
+            limit_price: Some(U128(price)),
+            max_spend: None,
+            quantity: U128(10 * NEAR_DENOMINATION),
+            side: Side::Sell,
+            order_type: OrderType::Limit,
+            client_id: None,
+            max_impact_bps: None,
+        }
+        .into_new_order(calc, sequence_number)
+    }
+
+    #[test]
+    fn doc_example_conversion() {
+        let order = doc_example_params().into_new_order(&near_usdc_calculator(), 1);
+        assert_eq!(order.limit_price_lots, None);
+        assert_eq!(order.available_quote_lots, Some(105_000));
+        assert_eq!(order.max_qty_lots, 10_000);
+        assert_eq!(order.order_type, OrderType::Market);
+        order.assert_valid();
+    }
+
+    #[test]
+    fn doc_example_fills_at_mid() {
+        let calc = near_usdc_calculator();
+        let maker = AccountId::new_unchecked("maker".to_string());
+        let taker = AccountId::new_unchecked("taker".to_string());
+        let mut ob = VecOrderbook::default();
+        ob.place_order(&maker, resting_ask(&calc, 1, 10 * USDC_DENOMINATION));
+
+        let res = ob.place_order(&taker, doc_example_params().into_new_order(&calc, 2));
+        assert_eq!(res.outcome, OrderOutcome::Filled);
+        assert_eq!(res.fill_qty_lots, 10_000);
+        // 10 NEAR at 10 USDC each
+        assert_eq!(
+            res.quote_amount_lots as u128 * calc.quote_lot_size,
+            100 * USDC_DENOMINATION
+        );
+    }
+
+    #[test]
+    fn doc_example_stops_at_max_spend() {
+        let calc = near_usdc_calculator();
+        let maker = AccountId::new_unchecked("maker".to_string());
+        let taker = AccountId::new_unchecked("taker".to_string());
+        let mut ob = VecOrderbook::default();
+        // 10% above mid, outside the 5% tolerance
+        ob.place_order(&maker, resting_ask(&calc, 1, 11 * USDC_DENOMINATION));
+
+        let res = ob.place_order(&taker, doc_example_params().into_new_order(&calc, 2));
+        // 105 USDC buys 9.545 NEAR at 11 USDC
+        assert_eq!(res.fill_qty_lots, 9_545);
+        assert!(res.quote_amount_lots as u128 * calc.quote_lot_size <= 105 * USDC_DENOMINATION);
+    }
+}
@@ -0,0 +1,114 @@
+/// Rebuild orderbook state from emitted events, eg, for shadowing a market
+/// off-chain.
+use near_sdk::Balance;
+use tonic_sdk_dex_events::{Event, EventType};
+use tonic_sdk_dex_types::{get_order_id_parts, LotBalance, OrderId};
+use tonic_sdk_macros::*;
+
+use crate::*;
+
+impl<T: L2> Orderbook<T> {
+    /// Apply a single event to the book. Events carry native quantities, so
+    /// the market's `base_lot_size` is needed to convert them back to lots.
+    ///
+    /// - `Order` posts the order's open quantity, if any, at the price encoded
+    ///   in its ID. Fills against resting orders come from `Fill` events.
+    /// - `Fill` reduces each maker order, removing it once fully filled.
+    /// - `Cancel` removes the cancelled orders.
+    /// - `Reduce` reduces the order, same as a fill.
+    /// - `Modify` removes the old order and posts its new quantity, if any, at
+    ///   the price encoded in the new ID, keeping the old order's owner and
+    ///   client ID.
+    ///
+    /// Events are not checked against the market, so callers should only pass
+    /// events for a single market.
+    pub fn apply_event(&mut self, event: &Event, base_lot_size: Balance) {
+        let to_lots = |quantity: Balance| {
+            _expect!(
+                conv::native_to_lots_checked(quantity, base_lot_size),
+                "lot balance overflow"
+            )
+        };
+        match &event.data {
+            EventType::Order(ev) => {
+                let open_quantity = _expect!(ev.open_quantity, "missing open quantity");
+                let open_qty_lots = to_lots(open_quantity.0);
+                if open_qty_lots == 0 {
+                    return;
+                }
+                let (side, price_lots, sequence_number) = get_order_id_parts(ev.order_id);
+                self.insert_order(OpenLimitOrder {
+                    sequence_number,
+                    owner_id: ev.account_id.clone(),
+                    open_qty_lots,
+                    client_id: ev.client_id,
+                    limit_price_lots: Some(price_lots),
+                    side: Some(side),
+                    price_rank: None,
+                });
+            }
+            EventType::Fill(ev) => {
+                for fill in ev.fills.iter() {
+                    self.reduce_order(fill.maker_order_id, to_lots(fill.fill_qty.0));
+                }
+            }
+            EventType::Cancel(ev) => {
+                for cancel in ev.cancels.iter() {
+                    let (side, price_lots, seq) = get_order_id_parts(cancel.order_id);
+                    self.side_mut(side).delete_order(price_lots, seq);
+                }
+            }
+            EventType::Reduce(ev) => {
+                self.reduce_order(ev.order_id, to_lots(ev.reduced_by.0));
+            }
+            EventType::Modify(ev) => {
+                let (side, price_lots, seq) = get_order_id_parts(ev.old_order_id);
+                let old = _expect!(
+                    self.side_mut(side).delete_order(price_lots, seq),
+                    "order not found"
+                );
+                let open_qty_lots = to_lots(ev.new_qty.0);
+                if open_qty_lots == 0 {
+                    return;
+                }
+                let (side, price_lots, sequence_number) = get_order_id_parts(ev.new_order_id);
+                self.insert_order(OpenLimitOrder {
+                    sequence_number,
+                    owner_id: old.owner_id,
+                    open_qty_lots,
+                    client_id: old.client_id,
+                    limit_price_lots: Some(price_lots),
+                    side: Some(side),
+                    price_rank: None,
+                });
+            }
+            EventType::NewMarket(_) => {}
+        }
+    }
+
+    /// Subtract filled or reduced quantity from a resting order, deleting it
+    /// if nothing is left open. Unlike the matching engine, this doesn't check
+    /// that the book is uncrossed, since a taker's order event may be applied
+    /// before the fills that uncross it.
+    fn reduce_order(&mut self, order_id: OrderId, qty_lots: LotBalance) {
+        let (side, price_lots, seq) = get_order_id_parts(order_id);
+        let l2 = self.side_mut(side);
+        let mut order = _expect!(l2.delete_order(price_lots, seq), "order not found");
+        order.open_qty_lots = order.open_qty_lots.saturating_sub(qty_lots);
+        if order.open_qty_lots > 0 {
+            l2.save_order(order);
+        }
+    }
+}
+
+impl VecOrderbook {
+    /// Build a book by applying events in order. See
+    /// [apply_event](Orderbook::apply_event).
+    pub fn replay(events: &[Event], base_lot_size: Balance) -> Self {
+        let mut ob = Self::default();
+        for event in events {
+            ob.apply_event(event, base_lot_size);
+        }
+        ob
+    }
+}
@@ -0,0 +1,80 @@
+/// Generator for order sequence numbers.
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use tonic_sdk_dex_types::{SequenceNumber, SEQUENCE_NUMBER_MAX};
+use tonic_sdk_macros::*;
+
+/// Hands out increasing sequence numbers, refusing to go past
+/// [SEQUENCE_NUMBER_MAX]. Beyond that, [new_order_id] would silently mask the
+/// top bit, producing an order ID on the wrong side.
+///
+/// [new_order_id]: tonic_sdk_dex_types::new_order_id
+#[derive(Clone, Debug, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct SequenceCounter {
+    next: SequenceNumber,
+}
+
+impl SequenceCounter {
+    /// Create a counter whose first sequence number is `start`.
+    pub fn new(start: SequenceNumber) -> Self {
+        Self { next: start }
+    }
+
+    /// Return the next sequence number, or [None] if the counter is
+    /// exhausted.
+    pub fn checked_next(&mut self) -> Option<SequenceNumber> {
+        if self.next > SEQUENCE_NUMBER_MAX {
+            return None;
+        }
+        let ret = self.next;
+        self.next += 1;
+        Some(ret)
+    }
+
+    /// Return the next sequence number.
+    ///
+    /// Panics if the counter is past [SEQUENCE_NUMBER_MAX].
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> SequenceNumber {
+        _expect!(self.checked_next(), "sequence number overflow")
+    }
+
+    /// The sequence number the next call to [next](Self::next) will return.
+    pub fn peek(&self) -> SequenceNumber {
+        self.next
+    }
+}
+
+/// Starts at 1, leaving 0 free to mean "no sequence number".
+impl Default for SequenceCounter {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn increments() {
+        let mut counter = SequenceCounter::default();
+        assert_eq!(counter.next(), 1);
+        assert_eq!(counter.next(), 2);
+
+        let mut counter = SequenceCounter::new(100);
+        assert_eq!(counter.peek(), 100);
+        assert_eq!(counter.next(), 100);
+        assert_eq!(counter.next(), 101);
+    }
+
+    // `next` panics via `env::panic_str`, which can't be caught in unit
+    // tests, so the cap is checked through `checked_next`.
+    #[test]
+    fn stops_at_max() {
+        let mut counter = SequenceCounter::new(SEQUENCE_NUMBER_MAX - 1);
+        assert_eq!(counter.next(), SEQUENCE_NUMBER_MAX - 1);
+        assert_eq!(counter.next(), SEQUENCE_NUMBER_MAX);
+        assert_eq!(counter.checked_next(), None);
+        assert_eq!(counter.checked_next(), None);
+    }
+}
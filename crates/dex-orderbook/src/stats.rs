@@ -0,0 +1,44 @@
+/// Lifetime trading statistics for a book.
+///
+/// Only compiled with the `stats` feature. Enabling it adds a field to
+/// [Orderbook], which changes its borsh layout, so existing books must be
+/// migrated when turning it on.
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::Balance;
+
+use crate::*;
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    BorshSerialize,
+    BorshDeserialize,
+    Serialize,
+    Deserialize,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OrderbookStats {
+    /// Total base traded, in lots.
+    pub total_base_volume_lots: u128,
+    /// Total quote traded, in native units.
+    pub total_quote_volume: Balance,
+    /// Number of maker fills.
+    pub trade_count: u64,
+}
+
+impl OrderbookStats {
+    pub(crate) fn record(&mut self, fill: &Match) {
+        self.total_base_volume_lots = self
+            .total_base_volume_lots
+            .saturating_add(fill.fill_qty_lots as u128);
+        self.total_quote_volume = self
+            .total_quote_volume
+            .saturating_add(fill.native_quote_paid);
+        self.trade_count = self.trade_count.saturating_add(1);
+    }
+}
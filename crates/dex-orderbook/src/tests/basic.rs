@@ -1,5 +1,7 @@
 pub use crate::*;
 
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+
 use super::test_utils::*;
 
 #[test]
@@ -17,6 +19,10 @@ fn add_order() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 10,
             base_lot_size: 1,
@@ -29,10 +35,8 @@ fn add_order() {
 #[test]
 fn no_fill() {
     let mut counter = new_counter();
-    let mut ob = new_orderbook();
-
-    add_orders(
-        &mut ob,
+    let ob = VecOrderbook::from_orders(
+        &AccountId::new_unchecked("test_user".to_string()),
         vec![
             NewOrder {
                 sequence_number: counter.next(),
@@ -42,6 +46,10 @@ fn no_fill() {
                 order_type: OrderType::Limit,
                 client_id: None,
                 available_quote_lots: None,
+                max_impact_bps: None,
+                max_matches: None,
+                max_price_levels: None,
+                notional_quote_lots: None,
                 quote_lot_size: 1,
                 base_denomination: 1,
                 base_lot_size: 1,
@@ -54,6 +62,10 @@ fn no_fill() {
                 order_type: OrderType::Limit,
                 client_id: None,
                 available_quote_lots: None,
+                max_impact_bps: None,
+                max_matches: None,
+                max_price_levels: None,
+                notional_quote_lots: None,
                 quote_lot_size: 1,
                 base_denomination: 1,
                 base_lot_size: 1,
@@ -66,6 +78,10 @@ fn no_fill() {
                 order_type: OrderType::Limit,
                 client_id: None,
                 available_quote_lots: None,
+                max_impact_bps: None,
+                max_matches: None,
+                max_price_levels: None,
+                notional_quote_lots: None,
                 quote_lot_size: 1,
                 base_denomination: 1,
                 base_lot_size: 1,
@@ -78,6 +94,10 @@ fn no_fill() {
                 order_type: OrderType::Limit,
                 client_id: None,
                 available_quote_lots: None,
+                max_impact_bps: None,
+                max_matches: None,
+                max_price_levels: None,
+                notional_quote_lots: None,
                 quote_lot_size: 1,
                 base_denomination: 1,
                 base_lot_size: 1,
@@ -90,6 +110,10 @@ fn no_fill() {
                 order_type: OrderType::Limit,
                 client_id: None,
                 available_quote_lots: None,
+                max_impact_bps: None,
+                max_matches: None,
+                max_price_levels: None,
+                notional_quote_lots: None,
                 quote_lot_size: 1,
                 base_denomination: 1,
                 base_lot_size: 1,
@@ -102,6 +126,10 @@ fn no_fill() {
                 order_type: OrderType::Limit,
                 client_id: None,
                 available_quote_lots: None,
+                max_impact_bps: None,
+                max_matches: None,
+                max_price_levels: None,
+                notional_quote_lots: None,
                 quote_lot_size: 1,
                 base_denomination: 1,
                 base_lot_size: 1,
@@ -127,6 +155,10 @@ fn basic_fill() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -145,6 +177,10 @@ fn basic_fill() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -162,6 +198,10 @@ fn basic_fill() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -176,10 +216,8 @@ fn basic_fill() {
 #[test]
 fn partial_fill() {
     let mut counter = new_counter();
-    let mut ob = new_orderbook();
-
-    add_orders(
-        &mut ob,
+    let mut ob = VecOrderbook::from_orders(
+        &AccountId::new_unchecked("test_user".to_string()),
         vec![
             NewOrder {
                 sequence_number: counter.next(),
@@ -189,6 +227,10 @@ fn partial_fill() {
                 order_type: OrderType::Limit,
                 client_id: None,
                 available_quote_lots: None,
+                max_impact_bps: None,
+                max_matches: None,
+                max_price_levels: None,
+                notional_quote_lots: None,
                 quote_lot_size: 1,
                 base_denomination: 1,
                 base_lot_size: 1,
@@ -201,6 +243,10 @@ fn partial_fill() {
                 order_type: OrderType::Limit,
                 client_id: None,
                 available_quote_lots: None,
+                max_impact_bps: None,
+                max_matches: None,
+                max_price_levels: None,
+                notional_quote_lots: None,
                 quote_lot_size: 1,
                 base_denomination: 1,
                 base_lot_size: 1,
@@ -213,6 +259,10 @@ fn partial_fill() {
                 order_type: OrderType::Limit,
                 client_id: None,
                 available_quote_lots: None,
+                max_impact_bps: None,
+                max_matches: None,
+                max_price_levels: None,
+                notional_quote_lots: None,
                 quote_lot_size: 1,
                 base_denomination: 1,
                 base_lot_size: 1,
@@ -229,6 +279,10 @@ fn partial_fill() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -259,6 +313,10 @@ fn find_order() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -276,6 +334,10 @@ fn find_order() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -294,10 +356,8 @@ fn find_order() {
 #[test]
 fn test_post_only() {
     let mut counter = new_counter();
-    let mut ob = new_orderbook();
-
-    add_orders(
-        &mut ob,
+    let mut ob = VecOrderbook::from_orders(
+        &AccountId::new_unchecked("test_user".to_string()),
         vec![NewOrder {
             sequence_number: counter.next(),
             limit_price_lots: Some(5),
@@ -306,6 +366,10 @@ fn test_post_only() {
             order_type: OrderType::PostOnly,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -321,6 +385,10 @@ fn test_post_only() {
             order_type: OrderType::PostOnly,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -340,6 +408,10 @@ fn test_post_only() {
             order_type: OrderType::PostOnly,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -353,10 +425,8 @@ fn test_post_only() {
 #[test]
 fn test_ioc() {
     let mut counter = new_counter();
-    let mut ob = new_orderbook();
-
-    add_orders(
-        &mut ob,
+    let mut ob = VecOrderbook::from_orders(
+        &AccountId::new_unchecked("test_user".to_string()),
         vec![NewOrder {
             sequence_number: counter.next(),
             limit_price_lots: Some(5),
@@ -365,6 +435,10 @@ fn test_ioc() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -380,6 +454,10 @@ fn test_ioc() {
             order_type: OrderType::ImmediateOrCancel,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -395,10 +473,8 @@ fn test_ioc() {
 #[test]
 fn test_fill_or_kill() {
     let mut counter = new_counter();
-    let mut ob = new_orderbook();
-
-    add_orders(
-        &mut ob,
+    let mut ob = VecOrderbook::from_orders(
+        &AccountId::new_unchecked("test_user".to_string()),
         vec![NewOrder {
             sequence_number: counter.next(),
             limit_price_lots: Some(5),
@@ -407,6 +483,10 @@ fn test_fill_or_kill() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -422,6 +502,10 @@ fn test_fill_or_kill() {
             order_type: OrderType::FillOrKill,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -441,6 +525,10 @@ fn test_fill_or_kill() {
             order_type: OrderType::FillOrKill,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -460,6 +548,10 @@ fn test_fill_or_kill() {
             order_type: OrderType::FillOrKill,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -486,6 +578,10 @@ fn test_cancel() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -513,6 +609,10 @@ fn test_cancel_multiple() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -530,6 +630,10 @@ fn test_cancel_multiple() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -547,6 +651,10 @@ fn test_cancel_multiple() {
             order_type: OrderType::Limit,
             client_id: None,
             available_quote_lots: None,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             quote_lot_size: 1,
             base_denomination: 1,
             base_lot_size: 1,
@@ -561,3 +669,1977 @@ fn test_cancel_multiple() {
     assert_eq!(ob.get_order(oid2), None, "Missed a spot (order 2)");
     assert_eq!(ob.get_order(oid3), None, "Missed a spot (order 3)");
 }
+
+#[test]
+fn test_new_order_dedup() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
+    use std::hash::{Hash, Hasher};
+
+    fn hash_of(order: &NewOrder) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        order.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let order = NewOrder {
+        client_id: Some(1),
+        available_quote_lots: Some(25),
+        ..new_order(1, Side::Buy, 5, 5)
+    };
+    let duplicate = order.clone();
+    let different = NewOrder {
+        sequence_number: 2,
+        ..order.clone()
+    };
+
+    assert_eq!(order, duplicate);
+    assert_eq!(hash_of(&order), hash_of(&duplicate), "hash mismatch");
+    assert_ne!(order, different);
+
+    let deduped: HashSet<NewOrder> = vec![order, duplicate, different].into_iter().collect();
+    assert_eq!(deduped.len(), 2);
+}
+
+#[test]
+fn test_match_native_base_filled() {
+    for base_lot_size in [1, 10, 1_000_000, 10u128.pow(16)] {
+        let mut counter = new_counter();
+        let mut ob = VecOrderbook::from_orders(
+            &AccountId::new_unchecked("test_user".to_string()),
+            vec![
+                NewOrder {
+                    base_lot_size,
+                    ..new_order(counter.next(), Side::Sell, 5, 3)
+                },
+                NewOrder {
+                    base_lot_size,
+                    ..new_order(counter.next(), Side::Sell, 6, 4)
+                },
+            ],
+        );
+        let res = ob.place_order(
+            &AccountId::new_unchecked("taker".to_string()),
+            NewOrder {
+                base_lot_size,
+                ..new_order(counter.next(), Side::Buy, 6, 5)
+            },
+        );
+
+        assert_eq!(res.matches.len(), 2);
+        for m in res.matches.iter() {
+            assert_eq!(
+                m.native_base_filled,
+                m.fill_qty_lots as u128 * base_lot_size,
+                "wrong native base filled for base lot size {}",
+                base_lot_size
+            );
+        }
+    }
+}
+
+#[test]
+fn test_with_capacity() {
+    let ob = VecOrderbook::with_capacity(8);
+    assert!(ob.bids.orders.capacity() >= 8);
+    assert!(ob.asks.orders.capacity() >= 8);
+    assert!(ob.bids.reverse_prices, "bids should be sorted descending");
+    assert!(!ob.asks.reverse_prices, "asks should be sorted ascending");
+}
+
+#[test]
+fn test_worst_price() {
+    let mut counter = new_counter();
+
+    let ob = new_orderbook();
+    assert_eq!(ob.worst_price_lots(Side::Buy), None);
+    assert_eq!(ob.worst_price_lots(Side::Sell), None);
+
+    let mut orders = vec![];
+    for (side, price) in [
+        (Side::Buy, 3),
+        (Side::Buy, 1),
+        (Side::Buy, 2),
+        (Side::Sell, 5),
+        (Side::Sell, 7),
+        (Side::Sell, 6),
+    ] {
+        orders.push(new_order(counter.next(), side, price, 1));
+    }
+    let ob = VecOrderbook::from_orders(&AccountId::new_unchecked("test_user".to_string()), orders);
+
+    assert_eq!(ob.worst_price_lots(Side::Buy), Some(1));
+    assert_eq!(ob.worst_price_lots(Side::Sell), Some(7));
+    assert_eq!(ob.find_bbo(Side::Buy).unwrap().unwrap_price(), 3);
+    assert_eq!(ob.find_bbo(Side::Sell).unwrap().unwrap_price(), 5);
+}
+
+#[test]
+fn test_has_orders_for() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let other = AccountId::new_unchecked("other".to_string());
+
+    assert!(!ob.has_orders_for(&maker));
+
+    let oid = place_order(&mut ob, &maker, new_order(counter.next(), Side::Sell, 5, 5));
+    assert!(ob.has_orders_for(&maker));
+    assert!(!ob.has_orders_for(&other));
+
+    ob.cancel_order(oid);
+    assert!(!ob.has_orders_for(&maker));
+}
+
+#[test]
+fn test_requires_settlement() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+
+    let posted = ob.place_order(
+        &AccountId::new_unchecked("maker".to_string()),
+        new_order(counter.next(), Side::Sell, 5, 5),
+    );
+    assert_eq!(posted.outcome, OrderOutcome::Posted);
+    assert!(posted.requires_settlement());
+
+    let rejected = ob.place_order(
+        &AccountId::new_unchecked("taker".to_string()),
+        NewOrder {
+            order_type: OrderType::FillOrKill,
+            ..new_order(counter.next(), Side::Buy, 5, 10)
+        },
+    );
+    assert_eq!(rejected.outcome, OrderOutcome::Rejected);
+    assert!(!rejected.requires_settlement());
+
+    let filled = ob.place_order(
+        &AccountId::new_unchecked("taker".to_string()),
+        new_order(counter.next(), Side::Buy, 5, 5),
+    );
+    assert_eq!(filled.outcome, OrderOutcome::Filled);
+    assert!(filled.requires_settlement());
+
+    // nothing to match and can't post, but locked funds need a refund
+    let unfilled_ioc = ob.place_order(
+        &AccountId::new_unchecked("taker".to_string()),
+        NewOrder {
+            order_type: OrderType::ImmediateOrCancel,
+            ..new_order(counter.next(), Side::Buy, 5, 5)
+        },
+    );
+    assert_eq!(unfilled_ioc.fill_qty_lots, 0);
+    assert!(unfilled_ioc.requires_settlement());
+}
+
+#[test]
+fn test_max_impact_bps() {
+    let mut counter = new_counter();
+
+    let mut orders = vec![];
+    for price in [100, 101, 102] {
+        orders.push(new_order(counter.next(), Side::Sell, price, 1));
+    }
+    let mut ob =
+        VecOrderbook::from_orders(&AccountId::new_unchecked("test_user".to_string()), orders);
+
+    // 1% band around the first fill (100) allows 101 but not 102
+    let res = ob.place_order(
+        &AccountId::new_unchecked("taker".to_string()),
+        NewOrder {
+            limit_price_lots: None,
+            order_type: OrderType::Market,
+            max_impact_bps: Some(100),
+            ..new_order(counter.next(), Side::Buy, 0, 3)
+        },
+    );
+    assert_eq!(res.outcome, OrderOutcome::PartialFill);
+    assert_eq!(res.fill_qty_lots, 2, "should stop before the 102 level");
+    assert_eq!(
+        res.matches
+            .iter()
+            .map(|m| m.fill_price_lots)
+            .collect::<Vec<_>>(),
+        vec![100, 101]
+    );
+    assert_eq!(ob.find_bbo(Side::Sell).unwrap().unwrap_price(), 102);
+}
+
+#[test]
+fn test_max_impact_bps_sell() {
+    let mut counter = new_counter();
+
+    let mut orders = vec![];
+    for price in [100, 99, 98] {
+        orders.push(new_order(counter.next(), Side::Buy, price, 1));
+    }
+    let mut ob =
+        VecOrderbook::from_orders(&AccountId::new_unchecked("test_user".to_string()), orders);
+
+    // band is 99..; the 98 level is out
+    let res = ob.place_order(
+        &AccountId::new_unchecked("taker".to_string()),
+        NewOrder {
+            limit_price_lots: None,
+            order_type: OrderType::Market,
+            max_impact_bps: Some(100),
+            ..new_order(counter.next(), Side::Sell, 0, 3)
+        },
+    );
+    assert_eq!(res.fill_qty_lots, 2, "should stop before the 98 level");
+    assert_eq!(ob.find_bbo(Side::Buy).unwrap().unwrap_price(), 98);
+}
+
+#[test]
+fn test_max_impact_bps_limit() {
+    let mut counter = new_counter();
+
+    let mut orders = vec![];
+    for price in [100, 101, 102] {
+        orders.push(new_order(counter.next(), Side::Sell, price, 1));
+    }
+    let mut ob =
+        VecOrderbook::from_orders(&AccountId::new_unchecked("test_user".to_string()), orders);
+
+    // the limit crosses 102, but the band stops matching before it. posting
+    // the remainder at 102 would cross the book
+    let res = ob.place_order(
+        &AccountId::new_unchecked("taker".to_string()),
+        NewOrder {
+            max_impact_bps: Some(100),
+            ..new_order(counter.next(), Side::Buy, 102, 3)
+        },
+    );
+    assert_eq!(res.fill_qty_lots, 2, "should stop before the 102 level");
+    assert_eq!(res.open_qty_lots, 0, "crossing remainder was posted");
+    assert_eq!(res.outcome, OrderOutcome::PartialFill);
+    assert!(ob.bids.is_empty());
+    assert!(!ob.is_crossed());
+    assert_eq!(ob.find_bbo(Side::Sell).unwrap().unwrap_price(), 102);
+}
+
+#[test]
+fn test_try_place_order_errors() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("test_user".to_string());
+
+    let mut order = |limit_price_lots: Option<u64>, max_qty_lots: u64, side: Side| NewOrder {
+        limit_price_lots,
+        ..new_order(counter.next(), side, 0, max_qty_lots)
+    };
+
+    assert_eq!(
+        ob.try_place_order(&user, order(None, 1, Side::Buy))
+            .unwrap_err(),
+        PlaceOrderError::MissingLimitPrice
+    );
+    assert_eq!(
+        ob.try_place_order(&user, order(Some(0), 1, Side::Buy))
+            .unwrap_err(),
+        PlaceOrderError::ZeroLimitPrice
+    );
+    assert_eq!(
+        ob.try_place_order(&user, order(Some(5), 0, Side::Buy))
+            .unwrap_err(),
+        PlaceOrderError::ZeroQuantity
+    );
+    assert!(ob.bids.is_empty() && ob.asks.is_empty(), "book modified");
+
+    let res = ob
+        .try_place_order(&user, order(Some(5), 1, Side::Sell))
+        .unwrap();
+    assert_eq!(res.outcome, OrderOutcome::Posted);
+
+    assert_eq!(
+        ob.try_place_order(&user, order(Some(5), 1, Side::Buy))
+            .unwrap_err(),
+        PlaceOrderError::SelfTrade
+    );
+    assert_eq!(
+        ob.get_order(res.id).unwrap().open_qty_lots,
+        1,
+        "book modified"
+    );
+    assert!(ob.bids.is_empty(), "book modified");
+}
+
+#[test]
+fn test_place_order_error_messages() {
+    assert_eq!(
+        PlaceOrderError::SelfTrade.to_string(),
+        tonic_sdk_dex_errors::SELF_TRADE
+    );
+    assert_eq!(
+        PlaceOrderError::MissingLimitPrice.to_string(),
+        tonic_sdk_dex_errors::MISSING_LIMIT_PRICE
+    );
+}
+
+#[test]
+fn test_imbalance() {
+    fn book(bids: &[(u64, u64)], asks: &[(u64, u64)]) -> VecOrderbook {
+        let mut counter = new_counter();
+        let mut orders = vec![];
+        for (side, levels) in [(Side::Buy, bids), (Side::Sell, asks)] {
+            for (price, qty) in levels {
+                orders.push(new_order(counter.next(), side, *price, *qty));
+            }
+        }
+        VecOrderbook::from_orders(&AccountId::new_unchecked("test_user".to_string()), orders)
+    }
+
+    assert_eq!(new_orderbook().imbalance(5), None);
+
+    let balanced = book(&[(9, 5), (8, 5)], &[(10, 5), (11, 5)]);
+    assert_eq!(balanced.imbalance(5), Some(0));
+
+    // 30 bid vs 10 ask
+    let bid_heavy = book(&[(9, 20), (8, 10)], &[(10, 10)]);
+    assert_eq!(bid_heavy.imbalance(5), Some(5_000));
+    // only the top level of each side: 20 vs 10
+    assert_eq!(bid_heavy.imbalance(1), Some(3_333));
+
+    let ask_heavy = book(&[(9, 10)], &[(10, 30)]);
+    assert_eq!(ask_heavy.imbalance(5), Some(-5_000));
+
+    let asks_only = book(&[], &[(10, 30)]);
+    assert_eq!(asks_only.imbalance(5), Some(-10_000));
+}
+
+#[test]
+fn test_insert_preserving_priority() {
+    let mut counter = new_counter();
+
+    // reserve sequence number 1 for the migrated order
+    let migrated_seq = counter.next();
+    let mut ob = VecOrderbook::from_orders(
+        &AccountId::new_unchecked("test_user".to_string()),
+        vec![new_order(counter.next(), Side::Sell, 5, 1)],
+    );
+
+    let migrated = OpenLimitOrder {
+        sequence_number: migrated_seq,
+        owner_id: AccountId::new_unchecked("migrated".to_string()),
+        open_qty_lots: 3,
+        client_id: Some(7),
+        limit_price_lots: Some(5),
+        side: Some(Side::Sell),
+        price_rank: None,
+    };
+    let migrated_id = migrated.id();
+    ob.insert_preserving_priority(migrated);
+
+    // older order is first in line at the shared price
+    let best = ob.find_bbo(Side::Sell).unwrap();
+    let first_at_level = ob.asks.iter().next().unwrap();
+    assert_eq!(first_at_level.id(), migrated_id);
+    assert_eq!(best.unwrap_price(), 5);
+
+    let found = ob.get_order(migrated_id).unwrap();
+    assert_eq!(found.open_qty_lots, 3);
+    assert_eq!(found.client_id, Some(7));
+
+    // and is filled first
+    let res = ob.place_order(
+        &AccountId::new_unchecked("taker".to_string()),
+        new_order(counter.next(), Side::Buy, 5, 1),
+    );
+    assert_eq!(res.matches[0].maker_order_id, migrated_id);
+}
+
+#[test]
+fn test_cancel_keeps_book_uncrossed() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+
+    let mut ids = vec![];
+    for (side, price) in [
+        (Side::Buy, 4),
+        (Side::Buy, 3),
+        (Side::Sell, 5),
+        (Side::Sell, 6),
+    ] {
+        ids.push(place_order(
+            &mut ob,
+            &maker,
+            new_order(counter.next(), side, price, 1),
+        ));
+    }
+    assert!(!ob.is_crossed());
+
+    for id in ids {
+        assert!(ob.cancel_order(id).is_some());
+        assert!(!ob.is_crossed());
+    }
+}
+
+#[test]
+fn test_is_crossed() {
+    let mut ob = new_orderbook();
+    assert!(!ob.is_crossed(), "empty book can't be crossed");
+
+    for (seq, side, price) in [(1, Side::Buy, 5), (2, Side::Sell, 5)] {
+        ob.insert_preserving_priority(OpenLimitOrder {
+            sequence_number: seq,
+            owner_id: AccountId::new_unchecked("maker".to_string()),
+            open_qty_lots: 1,
+            client_id: None,
+            limit_price_lots: Some(price),
+            side: Some(side),
+            price_rank: None,
+        });
+    }
+    assert!(ob.is_crossed());
+}
+
+#[test]
+fn test_from_orders() {
+    let mut counter = new_counter();
+    let mut order = |side: Side, price: u64| new_order(counter.next(), side, price, 1);
+    let orders = vec![
+        order(Side::Buy, 3),
+        order(Side::Buy, 4),
+        order(Side::Sell, 6),
+        order(Side::Sell, 5),
+    ];
+
+    let ob = VecOrderbook::from_orders(&AccountId::new_unchecked("maker".to_string()), orders);
+    assert_eq!(ob.find_bbo(Side::Buy).unwrap().unwrap_price(), 4);
+    assert_eq!(ob.find_bbo(Side::Sell).unwrap().unwrap_price(), 5);
+    assert!(ob.has_orders_for(&AccountId::new_unchecked("maker".to_string())));
+}
+
+#[test]
+fn test_remaining_native_value() {
+    let calc = OrderbookCalculator {
+        base_lot_size: 10,
+        quote_lot_size: 100,
+        base_denomination: 1_000,
+    };
+    let user = AccountId::new_unchecked("a.near".to_string());
+
+    let bid = OpenLimitOrder {
+        open_qty_lots: 5,
+        owner_id: user.clone(),
+        sequence_number: 1,
+        client_id: None,
+        side: Some(Side::Buy),
+        limit_price_lots: Some(100),
+        price_rank: None,
+    };
+    // 5 lots * 10 base * 100 price lots * 100 quote / 1000
+    assert_eq!(bid.remaining_native_value(&calc), 500);
+
+    let ask = OpenLimitOrder {
+        side: Some(Side::Sell),
+        limit_price_lots: Some(101),
+        ..bid
+    };
+    assert_eq!(ask.remaining_native_value(&calc), 50);
+}
+
+// the miss case panics via env::panic_str, which aborts under the mocked
+// blockchain, so only the hit case is tested here
+#[test]
+fn test_cancel_order_strict() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    let order_id = place_order(&mut ob, &user, new_order(counter.next(), Side::Sell, 10, 3));
+
+    let order = ob.cancel_order_strict(order_id);
+    assert_eq!(order.id(), order_id);
+    assert_eq!(order.open_qty_lots, 3);
+    assert!(ob.get_order(order_id).is_none());
+}
+
+#[test]
+fn test_client_id_in_view() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    let res = ob.place_order(
+        &user,
+        NewOrder {
+            client_id: Some(42),
+            ..new_order(counter.next(), Side::Sell, 10, 3)
+        },
+    );
+    assert_eq!(res.client_id, Some(42));
+
+    let view = res.into_view(1, 1);
+    assert_eq!(view.client_id, Some(42));
+    let json = near_sdk::serde_json::to_string(&view).unwrap();
+    assert!(json.contains("\"client_id\":42"), "{}", json);
+}
+
+#[test]
+fn test_max_matches() {
+    let mut counter = new_counter();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let mut order = |side: Side, qty: u64, order_type: OrderType, max_matches| NewOrder {
+        order_type,
+        available_quote_lots: match side {
+            Side::Buy => Some(qty * 10),
+            Side::Sell => None,
+        },
+        max_matches,
+        ..new_order(counter.next(), side, 10, qty)
+    };
+    // many small makers at the same level
+    let makers: Vec<NewOrder> = (0..10)
+        .map(|_| order(Side::Sell, 1, OrderType::Limit, None))
+        .collect();
+    let mut ob = VecOrderbook::from_orders(&maker, makers);
+
+    // limit: stops at the cap and doesn't post the crossing remainder
+    let res = ob.place_order(&taker, order(Side::Buy, 8, OrderType::Limit, Some(3)));
+    assert_eq!(res.matches.len(), 3);
+    assert_eq!(res.fill_qty_lots, 3);
+    assert_eq!(res.open_qty_lots, 0);
+    assert_eq!(res.outcome, OrderOutcome::PartialFill);
+    assert!(ob.find_bbo(Side::Buy).is_none(), "remainder posted");
+    assert!(!ob.is_crossed());
+    assert_eq!(ob.asks.iter().count(), 7);
+
+    // fill or kill: can't fill completely within the cap
+    let res = ob.place_order(&taker, order(Side::Buy, 4, OrderType::FillOrKill, Some(3)));
+    assert_eq!(res.outcome, OrderOutcome::Rejected);
+    assert_eq!(ob.asks.iter().count(), 7);
+
+    // under the cap: fills everything and posts the rest as usual
+    let res = ob.place_order(&taker, order(Side::Buy, 9, OrderType::Limit, Some(7)));
+    assert_eq!(res.matches.len(), 7);
+    assert_eq!(res.open_qty_lots, 2);
+    assert_eq!(res.outcome, OrderOutcome::PartialFill);
+    assert_eq!(ob.find_bbo(Side::Buy).unwrap().open_qty_lots, 2);
+}
+
+#[test]
+fn test_market() {
+    use near_sdk::json_types::U128;
+
+    let mut counter = new_counter();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    // 0.1 base lots, 0.01 quote lots, 6 base decimals
+    let mut market = Market::new(100_000, 10_000, 1_000_000);
+    let params = |side: Side, price: u128| NewOrderParams {
+        limit_price: Some(U128(price)),
+        max_spend: match side {
+            Side::Buy => Some(U128(100_000_000)),
+            Side::Sell => None,
+        },
+        quantity: U128(1_000_000),
+        side,
+        order_type: OrderType::Limit,
+        client_id: None,
+        max_impact_bps: None,
+    };
+
+    assert_eq!(market.midmarket_price_native(), None);
+
+    let res = market.place(&maker, params(Side::Buy, 9_500_000), counter.next());
+    assert_eq!(res.outcome, OrderOutcome::Posted);
+    assert_eq!(market.best_price_native(Side::Buy), Some(9_500_000));
+    assert_eq!(market.midmarket_price_native(), Some(9_500_000));
+
+    market.place(&maker, params(Side::Sell, 10_500_000), counter.next());
+    assert_eq!(market.best_price_native(Side::Sell), Some(10_500_000));
+    assert_eq!(market.midmarket_price_native(), Some(10_000_000));
+
+    market.cancel(res.id).unwrap();
+    assert_eq!(market.midmarket_price_native(), Some(10_500_000));
+}
+
+#[test]
+fn test_quote_spent_lots() {
+    assert_eq!(quote_spent_lots(Some(100), Some(40)), Some(60));
+    assert_eq!(quote_spent_lots(Some(100), None), Some(100));
+    assert_eq!(quote_spent_lots(None, None), Some(0));
+    // inconsistent: more left over than was available
+    assert_eq!(quote_spent_lots(Some(40), Some(100)), None);
+
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let mut order = |side: Side, price: u64, qty: u64, available_quote_lots| NewOrder {
+        order_type: match side {
+            Side::Buy => OrderType::ImmediateOrCancel,
+            Side::Sell => OrderType::Limit,
+        },
+        available_quote_lots,
+        ..new_order(counter.next(), side, price, qty)
+    };
+    ob.place_order(&maker, order(Side::Sell, 10, 3, None));
+    ob.place_order(&maker, order(Side::Sell, 12, 3, None));
+
+    // 3 @ 10 + 1 @ 12, with 8 quote lots left over
+    let res = ob.place_order(&taker, order(Side::Buy, 12, 4, Some(50)));
+    assert_eq!(res.fill_qty_lots, 4);
+    assert_eq!(res.quote_amount_lots, 42);
+}
+
+#[test]
+fn test_take_depth_for_owner() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let alice = AccountId::new_unchecked("alice".to_string());
+    let bob = AccountId::new_unchecked("bob".to_string());
+    for (user, price, qty) in [
+        (&alice, 10, 1),
+        (&bob, 10, 2),
+        (&bob, 11, 3),
+        (&alice, 12, 4),
+        (&alice, 12, 5),
+        (&bob, 13, 6),
+        (&alice, 14, 7),
+    ] {
+        place_order(
+            &mut ob,
+            user,
+            new_order(counter.next(), Side::Sell, price, qty),
+        );
+    }
+
+    let levels = ob.take_depth_for_owner(&alice, 2, Side::Sell);
+    let summary: Vec<(u64, Vec<u64>)> = levels
+        .iter()
+        .map(|(price, orders)| (*price, orders.iter().map(|o| o.open_qty_lots).collect()))
+        .collect();
+    assert_eq!(summary, vec![(10, vec![1]), (12, vec![4, 5])]);
+    assert!(levels
+        .iter()
+        .flat_map(|(_, orders)| orders.iter())
+        .all(|o| o.owner_id == alice));
+
+    assert_eq!(ob.take_depth_for_owner(&bob, 10, Side::Sell).len(), 3);
+    assert!(ob.take_depth_for_owner(&bob, 10, Side::Buy).is_empty());
+}
+
+#[test]
+fn test_stats() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let mut order = |side: Side, price: u64, qty: u64| NewOrder {
+        available_quote_lots: match side {
+            Side::Buy => Some(price * qty),
+            Side::Sell => None,
+        },
+        ..new_order(counter.next(), side, price, qty)
+    };
+    for (price, qty) in [(10, 2), (11, 3), (12, 4)] {
+        ob.place_order(&maker, order(Side::Sell, price, qty));
+    }
+    assert_eq!(ob.stats(), OrderbookStats::default());
+
+    let results = [
+        ob.place_order(&taker, order(Side::Buy, 11, 4)),
+        ob.place_order(&taker, order(Side::Buy, 12, 4)),
+        ob.place_order(&taker, order(Side::Buy, 12, 1)),
+    ];
+
+    let fills: Vec<&Match> = results.iter().flat_map(|r| r.matches.iter()).collect();
+    assert_eq!(fills.len(), 5);
+    let stats = ob.stats();
+    assert_eq!(stats.trade_count, 5);
+    assert_eq!(
+        stats.total_base_volume_lots,
+        fills.iter().map(|m| m.fill_qty_lots as u128).sum::<u128>()
+    );
+    assert_eq!(
+        stats.total_quote_volume,
+        fills.iter().map(|m| m.native_quote_paid).sum::<u128>()
+    );
+    // 2 @ 10, 2 @ 11, then 1 @ 11, 3 @ 12, then 1 @ 12
+    assert_eq!(stats.total_base_volume_lots, 9);
+    assert_eq!(stats.total_quote_volume, 101);
+}
+
+#[test]
+fn test_owner_fill_count() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let other = AccountId::new_unchecked("other".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let mut order = |side: Side, price: u64, qty: u64| NewOrder {
+        available_quote_lots: match side {
+            Side::Buy => Some(price * qty),
+            Side::Sell => None,
+        },
+        ..new_order(counter.next(), side, price, qty)
+    };
+    for price in [10, 11, 12, 13] {
+        ob.place_order(&maker, order(Side::Sell, price, 1));
+    }
+    ob.place_order(&other, order(Side::Sell, 11, 1));
+
+    let threshold = 2;
+    ob.place_order(&taker, order(Side::Buy, 11, 3));
+    assert_eq!(ob.owner_fill_count(&maker), 2);
+    assert_eq!(ob.owner_fill_count(&other), 1);
+    assert_eq!(ob.owner_fill_count(&taker), 0);
+
+    // maker hit the threshold: pull the remaining quotes
+    assert!(ob.owner_fill_count(&maker) >= threshold);
+    let cancelled = ob.cancel_all_for_owner(&maker);
+    assert_eq!(cancelled.len(), 2);
+
+    // counts aren't part of the book's state; the caller carries them over
+    let saved = ob.fill_counts().clone();
+    let mut ob = VecOrderbook::try_from_slice(&ob.try_to_vec().unwrap()).unwrap();
+    assert_eq!(ob.owner_fill_count(&maker), 0);
+    ob.set_fill_counts(saved);
+    assert_eq!(ob.owner_fill_count(&maker), 2);
+
+    ob.reset_fill_counts();
+    assert_eq!(ob.owner_fill_count(&maker), 0);
+    assert_eq!(ob.owner_fill_count(&other), 0);
+}
+
+#[test]
+fn test_clear_side() {
+    let mut counter = new_counter();
+    let mut order = |side: Side, price: u64| new_order(counter.next(), side, price, 1);
+    let orders = vec![
+        order(Side::Buy, 3),
+        order(Side::Buy, 4),
+        order(Side::Sell, 6),
+        order(Side::Sell, 5),
+        order(Side::Sell, 5),
+    ];
+    let mut ob = VecOrderbook::from_orders(&AccountId::new_unchecked("maker".to_string()), orders);
+
+    let removed = ob.clear_side(Side::Sell);
+    let removed: Vec<(u64, u64)> = removed
+        .iter()
+        .map(|o| (o.unwrap_price(), o.sequence_number))
+        .collect();
+    assert_eq!(removed, vec![(5, 4), (5, 5), (6, 3)]);
+    assert!(ob.asks.is_empty());
+    assert_eq!(ob.bids.iter().count(), 2);
+    assert_eq!(ob.best_price_lots(Side::Buy), Some(4));
+
+    assert!(ob.clear_side(Side::Sell).is_empty());
+}
+
+#[test]
+fn test_orderbook_eq() {
+    let build = |prices: &[u64]| {
+        let mut counter = new_counter();
+        let mut ob = new_orderbook();
+        let user = AccountId::new_unchecked("maker".to_string());
+        for price in prices {
+            ob.place_order(&user, new_order(counter.next(), Side::Sell, *price, 2));
+        }
+        ob
+    };
+
+    assert_eq!(new_orderbook(), new_orderbook());
+    assert_eq!(build(&[5, 6, 6]), build(&[5, 6, 6]));
+    assert_ne!(build(&[5, 6, 6]), build(&[5, 6, 7]));
+    assert_ne!(build(&[5, 6, 6]), build(&[5, 6]));
+
+    // a partially filled maker is saved with its price rank initialized;
+    // that shouldn't affect equality
+    let mut a = build(&[5, 6]);
+    let mut b = build(&[5, 6]);
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let buy = NewOrder {
+        order_type: OrderType::ImmediateOrCancel,
+        available_quote_lots: Some(5),
+        ..new_order(100, Side::Buy, 5, 1)
+    };
+    a.place_order(&taker, buy.clone());
+    assert_ne!(a, b);
+    b.place_order(&taker, buy);
+    assert_eq!(a, b);
+}
+
+#[test]
+fn test_refund_on_cancel() {
+    let calc = OrderbookCalculator {
+        base_lot_size: 10,
+        quote_lot_size: 1,
+        base_denomination: 1_000,
+    };
+    let user = AccountId::new_unchecked("a.near".to_string());
+
+    let ask = OpenLimitOrder {
+        open_qty_lots: 7,
+        owner_id: user,
+        sequence_number: 1,
+        client_id: None,
+        side: Some(Side::Sell),
+        limit_price_lots: Some(333),
+        price_rank: None,
+    };
+    // exact: 7 lots of 10
+    assert_eq!(ask.refund_on_cancel(&calc), 70);
+
+    let bid = OpenLimitOrder {
+        side: Some(Side::Buy),
+        ..ask
+    };
+    // 7 lots * 10 base * 333 quote / 1000 = 23.31, rounded down
+    assert_eq!(bid.refund_on_cancel(&calc), 23);
+    assert_eq!(
+        bid.refund_on_cancel(&calc),
+        bid.value_locked(10, 1, 1_000).quote_locked
+    );
+}
+
+#[test]
+fn test_markets() {
+    use near_sdk::json_types::U128;
+
+    let near_usdc = MarketId([1; 32]);
+    let eth_usdc = MarketId([2; 32]);
+    let mut markets = Markets::new(b"m".to_vec());
+    assert!(markets.is_empty());
+    assert!(markets
+        .insert(&near_usdc, &Market::new(100_000, 10_000, 1_000_000))
+        .is_none());
+    markets.insert(&eth_usdc, &Market::new(1_000, 100, 1_000_000_000));
+    assert_eq!(markets.len(), 2);
+
+    let maker = AccountId::new_unchecked("maker".to_string());
+    {
+        let mut market = markets.get_mut(&eth_usdc).unwrap();
+        let res = market.place(
+            &maker,
+            NewOrderParams {
+                limit_price: Some(U128(2_000_000_000)),
+                max_spend: None,
+                quantity: U128(1_000_000),
+                side: Side::Sell,
+                order_type: OrderType::Limit,
+                client_id: None,
+                max_impact_bps: None,
+            },
+            1,
+        );
+        assert_eq!(res.outcome, OrderOutcome::Posted);
+    }
+
+    let eth = markets.get(&eth_usdc).unwrap();
+    assert_eq!(eth.best_price_native(Side::Sell), Some(2_000_000_000));
+    assert_eq!(
+        eth.orderbook.find_bbo(Side::Sell).unwrap().open_qty_lots,
+        1_000
+    );
+    assert!(markets.get(&near_usdc).unwrap().orderbook.asks.is_empty());
+    assert!(markets.get(&MarketId([3; 32])).is_none());
+    assert!(markets.get_mut(&MarketId([3; 32])).is_none());
+
+    let mut ids: Vec<MarketId> = markets.iter().map(|(id, _)| id).collect();
+    ids.sort_by_key(|id| id.0);
+    assert_eq!(ids, vec![near_usdc, eth_usdc]);
+}
+
+#[test]
+fn test_all_orders_order() {
+    let alice = AccountId::new_unchecked("alice".to_string());
+    let bob = AccountId::new_unchecked("bob".to_string());
+    let build = || {
+        let mut ob = new_orderbook();
+        let mut counter = new_counter();
+        for (user, side, price) in [
+            (&alice, Side::Buy, 3),
+            (&bob, Side::Sell, 7),
+            (&bob, Side::Buy, 4),
+            (&alice, Side::Sell, 6),
+            (&alice, Side::Buy, 3),
+            (&bob, Side::Sell, 6),
+            (&bob, Side::Buy, 4),
+            (&alice, Side::Sell, 7),
+        ] {
+            ob.place_order(user, new_order(counter.next(), side, price, 1));
+        }
+        ob
+    };
+    let summary = |orders: &[OpenLimitOrder]| -> Vec<(Side, u64, u64)> {
+        orders
+            .iter()
+            .map(|o| (o.unwrap_side(), o.unwrap_price(), o.sequence_number))
+            .collect()
+    };
+    let expected = vec![
+        (Side::Sell, 6, 4),
+        (Side::Sell, 6, 6),
+        (Side::Sell, 7, 2),
+        (Side::Sell, 7, 8),
+        (Side::Buy, 4, 3),
+        (Side::Buy, 4, 7),
+        (Side::Buy, 3, 1),
+        (Side::Buy, 3, 5),
+    ];
+
+    let mut ob = build();
+    assert_eq!(summary(&ob.all_orders()), expected);
+    assert_eq!(summary(&ob.drain_all()), expected);
+    assert!(ob.all_orders().is_empty());
+
+    let mut ob = build();
+    let alice_orders: Vec<(Side, u64, u64)> = vec![
+        (Side::Sell, 6, 4),
+        (Side::Sell, 7, 8),
+        (Side::Buy, 3, 1),
+        (Side::Buy, 3, 5),
+    ];
+    assert_eq!(summary(&ob.cancel_all_for_owner(&alice)), alice_orders);
+    assert!(!ob.has_orders_for(&alice));
+    assert_eq!(ob.all_orders().len(), 4);
+}
+
+#[test]
+fn test_cancel_orders_with_tvl() {
+    let calc = OrderbookCalculator {
+        base_lot_size: 10,
+        quote_lot_size: 1,
+        base_denomination: 100,
+    };
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    let mut ids = vec![];
+    for (side, price, qty) in [
+        (Side::Buy, 10, 2),
+        (Side::Buy, 11, 3),
+        (Side::Sell, 20, 4),
+        (Side::Sell, 21, 5),
+    ] {
+        ids.push(place_order(
+            &mut ob,
+            &user,
+            NewOrder {
+                quote_lot_size: calc.quote_lot_size,
+                base_denomination: calc.base_denomination,
+                base_lot_size: calc.base_lot_size,
+                ..new_order(counter.next(), side, price, qty)
+            },
+        ));
+    }
+
+    // cancel one bid and one ask, plus an order that doesn't exist
+    let missing = new_order_id(Side::Buy, 1, 99);
+    let (cancelled, tvl) = ob.cancel_orders_with_tvl(vec![ids[1], ids[2], missing], &calc);
+    assert_eq!(cancelled.len(), 2);
+    assert_eq!(
+        tvl,
+        Tvl {
+            // 4 lots * 10
+            base_locked: 40,
+            // 3 lots * 10 * 11 / 100
+            quote_locked: 3,
+        }
+    );
+    let refunds: u128 = cancelled.iter().map(|o| o.refund_on_cancel(&calc)).sum();
+    assert_eq!(tvl.base_locked + tvl.quote_locked, refunds);
+    assert_eq!(ob.all_orders().len(), 2);
+}
+
+#[test]
+fn test_place_order_or_reject() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    let mut order = |limit_price_lots: Option<u64>, max_qty_lots: u64| NewOrder {
+        limit_price_lots,
+        ..new_order(counter.next(), Side::Sell, 0, max_qty_lots)
+    };
+    let batch = vec![
+        order(Some(10), 1),
+        order(Some(11), 0),
+        order(None, 1),
+        order(Some(12), 2),
+    ];
+
+    let outcomes: Vec<OrderOutcome> = batch
+        .into_iter()
+        .map(|order| ob.place_order_or_reject(&user, order).outcome)
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            OrderOutcome::Posted,
+            OrderOutcome::Rejected,
+            OrderOutcome::Rejected,
+            OrderOutcome::Posted,
+        ]
+    );
+    assert_eq!(ob.all_orders().len(), 2);
+}
+
+#[test]
+fn test_side_accessor() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    for (side, price) in [(Side::Buy, 10), (Side::Sell, 20)] {
+        place_order(&mut ob, &user, new_order(counter.next(), side, price, 1));
+    }
+
+    assert_eq!(ob.side(Side::Buy), &ob.bids);
+    assert_eq!(ob.side(Side::Sell), &ob.asks);
+    assert_eq!(ob.side(Side::Buy).max_order().unwrap().unwrap_price(), 10);
+
+    ob.side_mut(Side::Sell).delete_order(20, 2).unwrap();
+    assert!(ob.asks.is_empty());
+    assert!(!ob.bids.is_empty());
+}
+
+#[test]
+fn test_place_order_dry_run() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let order = |seq, side, qty| new_order(seq, side, 10, qty);
+    ob.place_order(&maker, order(counter.next(), Side::Sell, 3));
+    let digest = ob.digest();
+
+    let buy = order(counter.next(), Side::Buy, 5);
+    let preview = ob.place_order_opts(
+        &taker,
+        buy.clone(),
+        PlaceOpts {
+            dry_run: true,
+            ..Default::default()
+        },
+    );
+    assert_eq!(ob.digest(), digest, "dry run modified the book");
+    assert_eq!(preview.outcome, OrderOutcome::PartialFill);
+    assert_eq!(preview.fill_qty_lots, 3);
+    assert_eq!(preview.open_qty_lots, 2);
+    assert_eq!(preview.matches.len(), 1);
+
+    let placed = ob.place_order_opts(&taker, buy, PlaceOpts::default());
+    assert_ne!(ob.digest(), digest);
+    assert_eq!(placed.matches.len(), preview.matches.len());
+    assert_eq!(
+        placed.matches[0].did_remove_maker_order(),
+        preview.matches[0].did_remove_maker_order()
+    );
+    assert_eq!(placed.price_rank, preview.price_rank);
+    assert_eq!(placed.best_bid, preview.best_bid);
+    assert_eq!(placed.best_ask, preview.best_ask);
+    assert_eq!(placed.into_view(1, 1), preview.into_view(1, 1));
+}
+
+#[test]
+fn test_place_order_dry_run_new_level_rank() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    for price in [8, 12] {
+        ob.place_order(&maker, new_order(counter.next(), Side::Buy, price, 1));
+    }
+
+    // 10 is a new level between the two resting bids
+    let buy = new_order(counter.next(), Side::Buy, 10, 1);
+    let preview = ob.place_order_opts(
+        &maker,
+        buy.clone(),
+        PlaceOpts {
+            dry_run: true,
+            ..Default::default()
+        },
+    );
+    let placed = ob.place_order_opts(&maker, buy, PlaceOpts::default());
+    assert_eq!(preview.price_rank, Some(1));
+    assert_eq!(placed.price_rank, preview.price_rank);
+}
+
+#[test]
+fn test_reject_duplicate_client_id() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let other = AccountId::new_unchecked("other".to_string());
+    let order = |seq, price| NewOrder {
+        client_id: Some(7),
+        ..new_order(seq, Side::Buy, price, 1)
+    };
+    let enforce = PlaceOpts {
+        reject_duplicate_client_id: true,
+        ..Default::default()
+    };
+    let res = ob.try_place_order_opts(&maker, order(counter.next(), 10), enforce);
+    assert_eq!(res.unwrap().outcome, OrderOutcome::Posted);
+
+    let digest = ob.digest();
+    let res = ob.try_place_order_opts(&maker, order(counter.next(), 11), enforce);
+    assert_eq!(res.unwrap_err(), PlaceOrderError::DuplicateClientId);
+    assert_eq!(ob.digest(), digest);
+
+    // client IDs are per owner
+    let res = ob.try_place_order_opts(&other, order(counter.next(), 11), enforce);
+    assert_eq!(res.unwrap().outcome, OrderOutcome::Posted);
+
+    // not enforced by default
+    let res = ob.place_order_opts(&maker, order(counter.next(), 12), PlaceOpts::default());
+    assert_eq!(res.outcome, OrderOutcome::Posted);
+}
+
+#[test]
+fn test_fully_filled_maker() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    ob.place_order(&maker, new_order(counter.next(), Side::Sell, 10, 2));
+    ob.place_order(&maker, new_order(counter.next(), Side::Sell, 11, 2));
+
+    let buy = new_order(counter.next(), Side::Buy, 11, 3);
+    let pending = ob.match_order(&taker, &buy).unwrap();
+    assert_eq!(pending.matches.len(), 2);
+    assert!(pending
+        .matches
+        .iter()
+        .all(|m| m.fully_filled_maker().is_none()));
+
+    let res = ob.place_order(&taker, buy);
+    let filled: Vec<Option<bool>> = res.matches.iter().map(|m| m.fully_filled_maker()).collect();
+    assert_eq!(filled, vec![Some(true), Some(false)]);
+}
+
+#[test]
+fn test_max_price_levels() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    let order = |seq, price| NewOrder {
+        max_price_levels: Some(2),
+        ..new_order(seq, Side::Buy, price, 1)
+    };
+    for price in [10, 11] {
+        let res = ob.place_order(&user, order(counter.next(), price));
+        assert_eq!(res.outcome, OrderOutcome::Posted);
+    }
+    assert_eq!(ob.bids.unique_prices_count(), 2);
+
+    // a third level is rejected
+    let res = ob.place_order(&user, order(counter.next(), 12));
+    assert_eq!(res.outcome, OrderOutcome::Rejected);
+    assert_eq!(ob.bids.unique_prices_count(), 2);
+
+    // an existing level still accepts orders
+    let res = ob.place_order(&user, order(counter.next(), 11));
+    assert_eq!(res.outcome, OrderOutcome::Posted);
+    assert_eq!(ob.all_orders().len(), 3);
+
+    // the cap applies per side
+    let res = ob.place_order(
+        &user,
+        NewOrder {
+            side: Side::Sell,
+            ..order(counter.next(), 20)
+        },
+    );
+    assert_eq!(res.outcome, OrderOutcome::Posted);
+}
+
+#[test]
+fn test_max_price_levels_partial_fill() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    for price in [10, 11] {
+        ob.place_order(&maker, new_order(counter.next(), Side::Buy, price, 1));
+    }
+    ob.place_order(&maker, new_order(counter.next(), Side::Sell, 12, 1));
+
+    // the fill stands, but the remainder would be a third bid level
+    let res = ob.place_order(
+        &taker,
+        NewOrder {
+            max_price_levels: Some(2),
+            ..new_order(counter.next(), Side::Buy, 13, 3)
+        },
+    );
+    assert_eq!(res.outcome, OrderOutcome::PartialFill);
+    assert_eq!(res.fill_qty_lots, 1);
+    assert_eq!(res.open_qty_lots, 0);
+    assert!(ob.asks.is_empty());
+    assert_eq!(ob.bids.unique_prices_count(), 2);
+}
+
+#[test]
+fn test_view_diff() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    place_order(&mut ob, &user, new_order(counter.next(), Side::Buy, 10, 2));
+    let cancel_id = place_order(&mut ob, &user, new_order(counter.next(), Side::Buy, 9, 3));
+    place_order(&mut ob, &user, new_order(counter.next(), Side::Sell, 20, 1));
+    let before = ob.view(10);
+    assert_eq!(
+        before.bids,
+        vec![
+            L2Level {
+                price_lots: 10,
+                open_qty_lots: 2
+            },
+            L2Level {
+                price_lots: 9,
+                open_qty_lots: 3
+            },
+        ]
+    );
+    assert!(before.diff(&before).is_empty());
+
+    place_order(&mut ob, &user, new_order(counter.next(), Side::Buy, 11, 1));
+    place_order(&mut ob, &user, new_order(counter.next(), Side::Buy, 10, 4));
+    ob.cancel_order(cancel_id);
+    let after = ob.view(10);
+
+    assert_eq!(
+        before.diff(&after),
+        OrderbookDiff {
+            bids: vec![
+                LevelChange::Added(L2Level {
+                    price_lots: 11,
+                    open_qty_lots: 1
+                }),
+                LevelChange::Changed {
+                    price_lots: 10,
+                    old_qty_lots: 2,
+                    new_qty_lots: 6
+                },
+                LevelChange::Removed(L2Level {
+                    price_lots: 9,
+                    open_qty_lots: 3
+                }),
+            ],
+            asks: vec![],
+        }
+    );
+}
+
+#[test]
+fn test_levels_into_iter() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    let order = |seq, side, price| new_order(seq, side, price, 1);
+    let bid_9 = place_order(&mut ob, &user, order(counter.next(), Side::Buy, 9));
+    let bid_10 = place_order(&mut ob, &user, order(counter.next(), Side::Buy, 10));
+    let bid_10_2 = place_order(&mut ob, &user, order(counter.next(), Side::Buy, 10));
+    let ask_21 = place_order(&mut ob, &user, order(counter.next(), Side::Sell, 21));
+    let ask_20 = place_order(&mut ob, &user, order(counter.next(), Side::Sell, 20));
+
+    let levels: Vec<(Side, LotBalance, Vec<OrderId>)> = ob
+        .levels(10)
+        .into_iter()
+        .map(|(side, price, orders)| (side, price, orders.iter().map(|o| o.id()).collect()))
+        .collect();
+    assert_eq!(
+        levels,
+        vec![
+            (Side::Buy, 10, vec![bid_10, bid_10_2]),
+            (Side::Buy, 9, vec![bid_9]),
+            (Side::Sell, 20, vec![ask_20]),
+            (Side::Sell, 21, vec![ask_21]),
+        ]
+    );
+
+    let top: Vec<_> = ob
+        .levels(1)
+        .into_iter()
+        .map(|(side, price, _)| (side, price))
+        .collect();
+    assert_eq!(top, vec![(Side::Buy, 10), (Side::Sell, 20)]);
+}
+
+#[test]
+fn test_match_settlement() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let order = |seq, side, qty| NewOrder {
+        base_denomination: 100,
+        base_lot_size: 10,
+        ..new_order(seq, side, 50, qty)
+    };
+
+    // buy taker: 2 lots (20 base) at 50 quote lots per 100 base
+    ob.place_order(&maker, order(counter.next(), Side::Sell, 2));
+    let res = ob.place_order(&taker, order(counter.next(), Side::Buy, 2));
+    assert_eq!(
+        res.matches[0].settlement(Side::Buy),
+        MatchSettlement {
+            taker: BalanceDelta {
+                base: 20,
+                quote: -10
+            },
+            maker: BalanceDelta {
+                base: -20,
+                quote: 10
+            },
+        }
+    );
+
+    // sell taker
+    ob.place_order(&maker, order(counter.next(), Side::Buy, 3));
+    let res = ob.place_order(&taker, order(counter.next(), Side::Sell, 3));
+    let settlement = res.matches[0].settlement(Side::Sell);
+    assert_eq!(
+        settlement.taker,
+        BalanceDelta {
+            base: -30,
+            quote: 15
+        }
+    );
+    assert_eq!(
+        settlement.maker,
+        BalanceDelta {
+            base: 30,
+            quote: -15
+        }
+    );
+}
+
+#[test]
+fn test_take_depth_bucketed() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    for (side, price, qty) in [
+        (Side::Sell, 100, 1),
+        (Side::Sell, 109, 2),
+        (Side::Sell, 110, 3),
+        (Side::Sell, 125, 4),
+        (Side::Sell, 131, 5),
+        (Side::Buy, 99, 1),
+        (Side::Buy, 90, 2),
+        (Side::Buy, 89, 3),
+    ] {
+        ob.place_order(&user, new_order(counter.next(), side, price, qty));
+    }
+
+    // 100 and 109 share the first bucket; 110 starts the next
+    assert_eq!(
+        ob.asks.take_depth_bucketed(10, 10),
+        vec![(100, 3), (110, 3), (120, 4), (130, 5)]
+    );
+    assert_eq!(ob.asks.take_depth_bucketed(10, 2), vec![(100, 3), (110, 3)]);
+    assert_eq!(ob.asks.take_depth_bucketed(50, 10), vec![(100, 15)]);
+
+    // bids are best (highest) first
+    assert_eq!(ob.bids.take_depth_bucketed(10, 10), vec![(90, 3), (80, 3)]);
+    assert_eq!(ob.bids.take_depth_bucketed(1, 10).len(), 3);
+}
+
+#[test]
+fn test_estimate_slippage() {
+    let calc = OrderbookCalculator {
+        base_lot_size: 10,
+        quote_lot_size: 1,
+        base_denomination: 100,
+    };
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    for (side, price, qty) in [
+        (Side::Sell, 100, 2),
+        // two orders at one level count as one level crossed
+        (Side::Sell, 110, 1),
+        (Side::Sell, 110, 1),
+        (Side::Sell, 120, 2),
+        (Side::Buy, 90, 3),
+        (Side::Buy, 80, 3),
+    ] {
+        ob.place_order(
+            &user,
+            NewOrder {
+                quote_lot_size: calc.quote_lot_size,
+                base_denomination: calc.base_denomination,
+                base_lot_size: calc.base_lot_size,
+                ..new_order(counter.next(), side, price, qty)
+            },
+        );
+    }
+    let digest = ob.digest();
+
+    // 2 lots at 100, 1 at 110: (200 + 110) / 3
+    let buy = ob.estimate_slippage(Side::Buy, 3, &calc).unwrap();
+    assert_eq!(
+        buy,
+        SlippageEstimate {
+            avg_price: 103,
+            worst_price: 110,
+            levels_crossed: 2,
+        }
+    );
+    assert!(buy.avg_price <= buy.worst_price);
+
+    let buy = ob.estimate_slippage(Side::Buy, 6, &calc).unwrap();
+    assert_eq!(buy.avg_price, 110);
+    assert_eq!(buy.worst_price, 120);
+    assert_eq!(buy.levels_crossed, 3);
+
+    // within the best level there's no slippage
+    let buy = ob.estimate_slippage(Side::Buy, 1, &calc).unwrap();
+    assert_eq!(buy.avg_price, buy.worst_price);
+    assert_eq!(buy.levels_crossed, 1);
+
+    let sell = ob.estimate_slippage(Side::Sell, 4, &calc).unwrap();
+    assert_eq!(sell.avg_price, 87);
+    assert_eq!(sell.worst_price, 80);
+    assert!(sell.avg_price >= sell.worst_price);
+
+    // too shallow
+    assert_eq!(ob.estimate_slippage(Side::Buy, 7, &calc), None);
+    assert_eq!(ob.estimate_slippage(Side::Sell, 7, &calc), None);
+    assert_eq!(ob.digest(), digest);
+}
+
+#[test]
+fn test_qty_to_reach_price() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    for (side, price, qty) in [
+        (Side::Sell, 100, 2),
+        (Side::Sell, 100, 1),
+        (Side::Sell, 110, 4),
+        (Side::Sell, 120, 5),
+        (Side::Buy, 90, 3),
+        (Side::Buy, 80, 6),
+    ] {
+        ob.place_order(&user, new_order(counter.next(), side, price, qty));
+    }
+
+    // below the book
+    assert_eq!(ob.qty_to_reach_price(Side::Buy, 99), 0);
+    // exactly on a level, including it
+    assert_eq!(ob.qty_to_reach_price(Side::Buy, 100), 3);
+    assert_eq!(ob.qty_to_reach_price(Side::Buy, 110), 7);
+    // between levels
+    assert_eq!(ob.qty_to_reach_price(Side::Buy, 115), 7);
+    // beyond the book
+    assert_eq!(ob.qty_to_reach_price(Side::Buy, 1_000), 12);
+
+    assert_eq!(ob.qty_to_reach_price(Side::Sell, 90), 3);
+    assert_eq!(ob.qty_to_reach_price(Side::Sell, 85), 3);
+    assert_eq!(ob.qty_to_reach_price(Side::Sell, 1), 9);
+}
+
+#[test]
+fn test_sell_sized_by_notional() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let order = |seq, side, price: Option<u64>, qty| NewOrder {
+        limit_price_lots: price,
+        ..new_order(seq, side, 0, qty)
+    };
+    ob.place_order(&maker, order(counter.next(), Side::Buy, Some(10), 5));
+    ob.place_order(&maker, order(counter.next(), Side::Buy, Some(9), 5));
+
+    // 30 quote at a limit of 9 is 3 lots, all filled at 10
+    let res = ob.place_order(
+        &taker,
+        NewOrder {
+            notional_quote_lots: Some(30),
+            ..order(counter.next(), Side::Sell, Some(9), u64::MAX)
+        },
+    );
+    assert_eq!(res.outcome, OrderOutcome::Filled);
+    assert_eq!(res.fill_qty_lots, 3);
+    let quote_received: u128 = res.matches.iter().map(|m| m.native_quote_paid).sum();
+    assert_eq!(quote_received, 30);
+
+    // max_qty_lots still caps the order
+    let res = ob.place_order(
+        &taker,
+        NewOrder {
+            notional_quote_lots: Some(30),
+            ..order(counter.next(), Side::Sell, Some(9), 1)
+        },
+    );
+    assert_eq!(res.fill_qty_lots, 1);
+
+    // market orders convert at the best bid
+    let res = ob.place_order(
+        &taker,
+        NewOrder {
+            order_type: OrderType::Market,
+            notional_quote_lots: Some(19),
+            ..order(counter.next(), Side::Sell, None, u64::MAX)
+        },
+    );
+    assert_eq!(res.fill_qty_lots, 1);
+
+    // not enough for a single lot
+    let res = ob.place_order(
+        &taker,
+        NewOrder {
+            notional_quote_lots: Some(8),
+            ..order(counter.next(), Side::Sell, Some(9), u64::MAX)
+        },
+    );
+    assert_eq!(res.outcome, OrderOutcome::Rejected);
+    assert_eq!(ob.bids.iter().map(|o| o.open_qty_lots).sum::<u64>(), 5);
+
+    // zero quantity orders without a notional aren't rejected
+    let res = ob.place_order(&taker, order(counter.next(), Side::Sell, Some(9), 0));
+    assert_eq!(res.outcome, OrderOutcome::Filled);
+    assert_eq!(res.fill_qty_lots, 0);
+    assert_eq!(ob.bids.iter().map(|o| o.open_qty_lots).sum::<u64>(), 5);
+}
+
+#[test]
+fn test_max_sequence_number() {
+    let mut ob = new_orderbook();
+    assert_eq!(ob.max_sequence_number(), None);
+
+    // migrate orders from another book, keeping their sequence numbers
+    let maker = AccountId::new_unchecked("maker".to_string());
+    for (seq, side, price) in [(7, Side::Buy, 5), (42, Side::Sell, 9), (13, Side::Sell, 8)] {
+        ob.insert_preserving_priority(OpenLimitOrder {
+            sequence_number: seq,
+            owner_id: maker.clone(),
+            open_qty_lots: 1,
+            client_id: None,
+            limit_price_lots: Some(price),
+            side: Some(side),
+            price_rank: None,
+        });
+    }
+    assert_eq!(ob.max_sequence_number(), Some(42));
+
+    let existing: Vec<OrderId> = ob.all_orders().iter().map(|o| o.id()).collect();
+    let mut counter = SequenceCounter::new(ob.max_sequence_number().unwrap() + 1);
+    for price in [4, 5, 6] {
+        let id = place_order(
+            &mut ob,
+            &maker,
+            NewOrder {
+                order_type: OrderType::PostOnly,
+                ..new_order(counter.next(), Side::Buy, price, 1)
+            },
+        );
+        assert!(!existing.contains(&id));
+    }
+    assert_eq!(ob.max_sequence_number(), Some(45));
+}
+
+#[test]
+fn test_split_order() {
+    let parent = NewOrder {
+        available_quote_lots: Some(101),
+        ..new_order(1, Side::Buy, 10, 10)
+    };
+    let children = split_order(parent.clone(), 3);
+    let qtys: Vec<LotBalance> = children.iter().map(|c| c.max_qty_lots).collect();
+    assert_eq!(qtys, vec![3, 3, 3, 1]);
+    let budget: LotBalance = children
+        .iter()
+        .map(|c| c.available_quote_lots.unwrap())
+        .sum();
+    assert_eq!(budget, 101);
+    assert!(children
+        .iter()
+        .all(|c| c.notional_quote_lots.is_none() && c.limit_price_lots == parent.limit_price_lots));
+
+    let sell = NewOrder {
+        side: Side::Sell,
+        available_quote_lots: None,
+        notional_quote_lots: Some(7),
+        ..parent
+    };
+    let children = split_order(sell, 4);
+    assert_eq!(
+        children.iter().map(|c| c.max_qty_lots).sum::<LotBalance>(),
+        10
+    );
+    assert_eq!(
+        children
+            .iter()
+            .map(|c| c.notional_quote_lots.unwrap())
+            .sum::<LotBalance>(),
+        7
+    );
+    assert!(children.iter().all(|c| c.available_quote_lots.is_none()));
+}
+
+#[test]
+fn test_ranked_snapshot() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    let order = |seq, side, price| new_order(seq, side, price, 1);
+    assert!(ob.ranked_snapshot().is_empty());
+    for price in [9, 10, 10, 7] {
+        place_order(&mut ob, &user, order(counter.next(), Side::Buy, price));
+    }
+    for price in [20, 21, 20, 25] {
+        place_order(&mut ob, &user, order(counter.next(), Side::Sell, price));
+    }
+
+    let snapshot = ob.ranked_snapshot();
+    assert_eq!(snapshot.len(), 8);
+    for (id, rank) in snapshot.iter().copied() {
+        let order = ob.get_order(id).unwrap();
+        let side = order.unwrap_side();
+        assert_eq!(rank, ob.side(side).get_price_rank(order.unwrap_price()));
+        assert_eq!(rank, order.unwrap_price_rank());
+    }
+    let ranks: Vec<u32> = snapshot.iter().map(|(_, rank)| *rank).collect();
+    assert_eq!(ranks, vec![0, 0, 1, 2, 0, 0, 1, 2]);
+}
+
+#[test]
+fn test_market_buy_budget_exhausted() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let order = |seq, side, order_type, qty, budget| NewOrder {
+        limit_price_lots: match order_type {
+            OrderType::Market => None,
+            _ => Some(10),
+        },
+        order_type,
+        available_quote_lots: budget,
+        ..new_order(seq, side, 10, qty)
+    };
+    ob.place_order(
+        &maker,
+        order(counter.next(), Side::Sell, OrderType::Limit, 10, None),
+    );
+
+    // enough budget for 2 of the 4 lots
+    let res = ob.place_order(
+        &taker,
+        order(counter.next(), Side::Buy, OrderType::Market, 4, Some(25)),
+    );
+    assert_eq!(res.outcome, OrderOutcome::PartialFill);
+    assert_eq!(res.fill_qty_lots, 2);
+    assert!(res.budget_exhausted);
+
+    let res = ob.place_order(
+        &taker,
+        order(counter.next(), Side::Buy, OrderType::Market, 4, Some(40)),
+    );
+    assert_eq!(res.outcome, OrderOutcome::Filled);
+    assert_eq!(res.fill_qty_lots, 4);
+    assert!(!res.budget_exhausted);
+}
+
+#[test]
+fn test_merge_results() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let order = |seq, side, order_type, price, qty| NewOrder {
+        limit_price_lots: price,
+        order_type,
+        client_id: Some(3),
+        available_quote_lots: match side {
+            Side::Buy => Some(100),
+            Side::Sell => None,
+        },
+        ..new_order(seq, side, 0, qty)
+    };
+    for price in [10, 11] {
+        ob.place_order(
+            &maker,
+            order(counter.next(), Side::Sell, OrderType::Limit, Some(price), 2),
+        );
+    }
+
+    // two slices of a market buy, each filling less than requested
+    let slice = |seq| order(seq, Side::Buy, OrderType::Market, None, 3);
+    let mut total = ob.place_order(&taker, slice(counter.next()));
+    let first_id = total.id;
+    let second = ob.place_order(&taker, slice(counter.next()));
+    assert_eq!((total.fill_qty_lots, second.fill_qty_lots), (3, 1));
+    assert_eq!(
+        (total.quote_amount_lots, second.quote_amount_lots),
+        (31, 11)
+    );
+
+    total.merge(second);
+    assert_eq!(total.id, first_id);
+    assert_eq!(total.client_id, Some(3));
+    assert_eq!(total.fill_qty_lots, 4);
+    assert_eq!(total.quote_amount_lots, 42);
+    assert_eq!(total.matches.len(), 3);
+    assert_eq!(
+        total
+            .matches
+            .iter()
+            .map(|m| m.fill_qty_lots)
+            .sum::<LotBalance>(),
+        total.fill_qty_lots
+    );
+    assert_eq!(total.outcome, OrderOutcome::PartialFill);
+    assert_eq!(total.best_ask, None);
+
+    // a later slice finding an empty book doesn't hide the earlier fills
+    total.merge(ob.place_order(&taker, slice(counter.next())));
+    assert_eq!(total.fill_qty_lots, 4);
+    assert_eq!(total.outcome, OrderOutcome::PartialFill);
+}
+
+#[test]
+fn test_check_fill_totals() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let order = |seq, side, price, budget| NewOrder {
+        available_quote_lots: budget,
+        // 1 base lot at price 1 costs 1/3 of a quote lot, so fills don't
+        // land on quote lot boundaries
+        quote_lot_size: 10,
+        base_denomination: 30,
+        ..new_order(seq, side, price, 4)
+    };
+    ob.place_order(&maker, order(counter.next(), Side::Sell, 7, None));
+    let buy = order(counter.next(), Side::Buy, 7, Some(100));
+    let mut res = ob.place_order(&taker, buy.clone());
+    assert_eq!(res.fill_qty_lots, 4);
+    // 4 * 7 * 10 / 30 = 9 native quote, rounded up to 1 lot
+    assert_eq!(res.matches[0].native_quote_paid, 9);
+    assert_eq!(res.quote_amount_lots, 1);
+    assert_eq!(check_fill_totals(&buy, &res), Ok(()));
+
+    res.quote_amount_lots += 1;
+    assert!(check_fill_totals(&buy, &res).is_err());
+    res.quote_amount_lots -= 1;
+    res.fill_qty_lots -= 1;
+    assert!(check_fill_totals(&buy, &res).is_err());
+}
+
+#[test]
+fn test_view_from_offset() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    let order = |seq, side, price| new_order(seq, side, price, 1);
+    // 50 bid levels, with two orders on every third level
+    for price in 1..=50 {
+        place_order(&mut ob, &user, order(counter.next(), Side::Buy, price));
+        if price % 3 == 0 {
+            place_order(&mut ob, &user, order(counter.next(), Side::Buy, price));
+        }
+    }
+    place_order(&mut ob, &user, order(counter.next(), Side::Sell, 100));
+
+    let mut paged: Vec<L2Level> = vec![];
+    let mut start = 0;
+    loop {
+        let page = ob.view_from_offset(Side::Buy, start, 7);
+        assert!(page.asks.is_empty());
+        let len = page.bids.len();
+        paged.extend(page.bids);
+        if len < 7 {
+            break;
+        }
+        start += 7;
+    }
+    assert_eq!(paged, ob.view(usize::MAX).bids);
+    assert_eq!(paged.len(), 50);
+    assert_eq!(paged[0].price_lots, 50);
+    assert_eq!(paged[0].open_qty_lots, 1);
+    assert_eq!(paged[2].open_qty_lots, 2);
+
+    let page = ob.view_from_offset(Side::Sell, 0, 7);
+    assert!(page.bids.is_empty());
+    assert_eq!(
+        page.asks,
+        vec![L2Level {
+            price_lots: 100,
+            open_qty_lots: 1
+        }]
+    );
+    assert!(ob.view_from_offset(Side::Sell, 1, 7).asks.is_empty());
+}
+
+#[test]
+fn test_batch_results_carry_client_id() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let order = |seq, side, price, client_id| NewOrder {
+        client_id,
+        ..new_order(seq, side, price, 1)
+    };
+    ob.place_order(&maker, order(counter.next(), Side::Sell, 10, None));
+
+    // a filled, a posted, a rejected, and an order without a client ID
+    let batch = vec![
+        order(counter.next(), Side::Buy, 10, Some(1)),
+        order(counter.next(), Side::Buy, 9, Some(2)),
+        NewOrder {
+            max_qty_lots: 0,
+            ..order(counter.next(), Side::Buy, 9, Some(3))
+        },
+        order(counter.next(), Side::Buy, 8, None),
+    ];
+    let results: Vec<(OrderOutcome, Option<ClientId>)> = batch
+        .into_iter()
+        .map(|order| ob.place_order_or_reject(&taker, order))
+        .map(|res| (res.outcome, res.client_id))
+        .collect();
+    assert_eq!(
+        results,
+        vec![
+            (OrderOutcome::Filled, Some(1)),
+            (OrderOutcome::Posted, Some(2)),
+            (OrderOutcome::Rejected, Some(3)),
+            (OrderOutcome::Posted, None),
+        ]
+    );
+}
+
+#[test]
+fn test_value_locked_by_level() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let user = AccountId::new_unchecked("maker".to_string());
+    let calc = OrderbookCalculator {
+        base_lot_size: 10,
+        quote_lot_size: 100,
+        base_denomination: 1_000,
+    };
+    let order = |seq, side, price, qty| NewOrder {
+        quote_lot_size: calc.quote_lot_size,
+        base_denomination: calc.base_denomination,
+        base_lot_size: calc.base_lot_size,
+        ..new_order(seq, side, price, qty)
+    };
+    for (side, price, qty) in [
+        (Side::Buy, 10, 5),
+        (Side::Buy, 10, 3),
+        (Side::Buy, 9, 2),
+        (Side::Sell, 12, 4),
+        (Side::Sell, 15, 1),
+    ] {
+        place_order(&mut ob, &user, order(counter.next(), side, price, qty));
+    }
+
+    let levels = ob.value_locked_by_level(&calc);
+    let quote = |qty, price| get_bid_quote_value(qty, price, 10, 100, 1_000);
+    assert_eq!(
+        levels,
+        vec![
+            (
+                Side::Buy,
+                10,
+                Tvl {
+                    base_locked: 0,
+                    quote_locked: quote(8, 10)
+                }
+            ),
+            (
+                Side::Buy,
+                9,
+                Tvl {
+                    base_locked: 0,
+                    quote_locked: quote(2, 9)
+                }
+            ),
+            (
+                Side::Sell,
+                12,
+                Tvl {
+                    base_locked: 40,
+                    quote_locked: 0
+                }
+            ),
+            (
+                Side::Sell,
+                15,
+                Tvl {
+                    base_locked: 10,
+                    quote_locked: 0
+                }
+            ),
+        ]
+    );
+    let total: Tvl = levels.iter().map(|(_, _, tvl)| tvl).sum();
+    assert_eq!(
+        total,
+        ob.value_locked(
+            calc.base_lot_size,
+            calc.quote_lot_size,
+            calc.base_denomination
+        )
+    );
+}
+
+#[test]
+fn test_sell_quote_budget_cleared() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let order = |seq, side, budget| NewOrder {
+        available_quote_lots: budget,
+        ..new_order(seq, side, 10, 5)
+    };
+
+    let mut sell = order(counter.next(), Side::Sell, Some(1));
+    sell.normalize();
+    assert_eq!(sell.available_quote_lots, None);
+    let mut buy = order(counter.next(), Side::Buy, Some(1));
+    buy.normalize();
+    assert_eq!(buy.available_quote_lots, Some(1));
+
+    // a budget of 1 would cap the fill at 0 lots if treated as a buy
+    ob.place_order(&maker, order(counter.next(), Side::Buy, Some(50)));
+    let res = ob.place_order(&taker, order(counter.next(), Side::Sell, Some(1)));
+    assert_eq!(res.outcome, OrderOutcome::Filled);
+    assert_eq!(res.fill_qty_lots, 5);
+    assert_eq!(res.quote_amount_lots, 0);
+}
+
+#[cfg(feature = "debug_log")]
+#[test]
+fn test_match_trace() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let order = |seq, side, price, qty, budget| NewOrder {
+        available_quote_lots: budget,
+        ..new_order(seq, side, price, qty)
+    };
+
+    ob.place_order(&maker, order(counter.next(), Side::Sell, 10, 5, None));
+    ob.place_order(&maker, order(counter.next(), Side::Sell, 11, 5, None));
+    near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new().build());
+
+    let res = ob.place_order(&taker, order(counter.next(), Side::Buy, 11, 8, Some(100)));
+    assert_eq!(res.fill_qty_lots, 8);
+    let trace: Vec<String> = near_sdk::test_utils::get_logs()
+        .into_iter()
+        // logs are prefixed with their source location
+        .filter_map(|l| l.find("match: ").map(|i| l[i..].to_string()))
+        .collect();
+    assert_eq!(
+        trace,
+        vec![
+            "match: maker 1 (5 lots) at 10, crossed: true",
+            "match: traded 5 lots at 10, 3 lots unfilled, quote remaining: Some(50)",
+            "match: maker 2 (5 lots) at 11, crossed: true",
+            "match: traded 3 lots at 11, 0 lots unfilled, quote remaining: Some(17)",
+        ]
+    );
+}
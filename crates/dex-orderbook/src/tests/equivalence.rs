@@ -0,0 +1,171 @@
+//! Run the same operations against each [L2] backend and check that they
+//! behave identically.
+use proptest::prelude::*;
+
+use super::fuzz::{arb_limit_order_vecs, place_or_check_rejected};
+use super::test_utils::*;
+use crate::l2::btree::BTreeL2;
+use crate::l2::vec::VecL2;
+
+enum Op {
+    Place(AccountId, NewOrder),
+    /// Cancel the order placed at this index of the op list.
+    Cancel(usize),
+}
+
+/// Assert that both books hold the same orders, as seen through the [L2]
+/// trait and the orderbook.
+fn assert_books_agree(a: &VecOrderbook, b: &BTreeOrderbook, step: usize) {
+    for side in [Side::Buy, Side::Sell].iter().copied() {
+        let (l, r) = (a.side(side), b.side(side));
+        assert_eq!(
+            l.take_depth(usize::MAX),
+            r.take_depth(usize::MAX),
+            "{:?} depth differs at step {}",
+            side,
+            step
+        );
+        assert_eq!(
+            a.find_bbo(side),
+            b.find_bbo(side),
+            "{:?} bbo differs at step {}",
+            side,
+            step
+        );
+        assert_eq!(l.max_order(), r.max_order(), "step {}", step);
+        assert_eq!(l.min_order(), r.min_order(), "step {}", step);
+        assert_eq!(l.peek_max(), r.peek_max(), "step {}", step);
+        assert_eq!(l.peek_min(), r.peek_min(), "step {}", step);
+        assert_eq!(l.price_ranks(), r.price_ranks(), "step {}", step);
+        assert_eq!(
+            l.unique_prices_count(),
+            r.unique_prices_count(),
+            "step {}",
+            step
+        );
+    }
+    assert_eq!(a.digest(), b.digest(), "digest differs at step {}", step);
+}
+
+/// Apply the ops to a book of each backend, checking results and book state
+/// after every step. Returns the final books.
+fn run_ops(ops: Vec<Op>) -> (VecOrderbook, BTreeOrderbook) {
+    let mut a = VecOrderbook::default();
+    let mut b = BTreeOrderbook::default();
+    let mut placed: Vec<Option<OrderId>> = vec![];
+
+    for (step, op) in ops.into_iter().enumerate() {
+        match op {
+            Op::Place(user, order) => {
+                let l = place_or_check_rejected(&mut a, &user, order.clone());
+                let r = place_or_check_rejected(&mut b, &user, order);
+                let (l, r) = match (l, r) {
+                    (Some(l), Some(r)) => (l, r),
+                    _ => {
+                        placed.push(None);
+                        continue;
+                    }
+                };
+                assert_eq!(l.id, r.id, "step {}", step);
+                assert_eq!(l.outcome, r.outcome, "step {}", step);
+                assert_eq!(l.fill_qty_lots, r.fill_qty_lots, "step {}", step);
+                assert_eq!(l.quote_amount_lots, r.quote_amount_lots, "step {}", step);
+                assert_eq!(l.open_qty_lots, r.open_qty_lots, "step {}", step);
+                assert_eq!(l.price_rank, r.price_rank, "step {}", step);
+                placed.push(Some(l.id));
+            }
+            // the order may have been rejected
+            Op::Cancel(idx) if placed[idx].is_none() => {
+                placed.push(None);
+            }
+            Op::Cancel(idx) => {
+                let id = placed[idx].unwrap();
+                let l = a.cancel_order(id).map(|res| res.order);
+                let r = b.cancel_order(id).map(|res| res.order);
+                assert_eq!(l, r, "step {}", step);
+                placed.push(None);
+            }
+        }
+        assert_books_agree(&a, &b, step);
+    }
+    (a, b)
+}
+
+/// Converting one book's sides must produce the other book's sides.
+fn assert_conversions_agree(a: VecOrderbook, b: BTreeOrderbook) {
+    assert_eq!(BTreeL2::from(a.bids.clone()), b.bids);
+    assert_eq!(BTreeL2::from(a.asks.clone()), b.asks);
+    assert_eq!(VecL2::from(b.bids), a.bids);
+    assert_eq!(VecL2::from(b.asks), a.asks);
+}
+
+#[test]
+fn backends_agree() {
+    let mut counter = new_counter();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let seller = AccountId::new_unchecked("seller".to_string());
+    let mut place = |user: &AccountId, side, price, qty| {
+        let seq = counter.next();
+        let order = NewOrder {
+            client_id: Some(seq as u32),
+            ..new_order(seq, side, price, qty)
+        };
+        Op::Place(user.clone(), order)
+    };
+
+    let ops = vec![
+        place(&maker, Side::Sell, 12, 5),
+        place(&maker, Side::Sell, 11, 3),
+        place(&maker, Side::Sell, 11, 4),
+        place(&maker, Side::Buy, 9, 6),
+        place(&maker, Side::Buy, 8, 2),
+        place(&maker, Side::Buy, 9, 1),
+        // cancel the bid at 8, leaving one bid level
+        Op::Cancel(4),
+        // fully fills the first ask at 11, partially fills the second
+        place(&taker, Side::Buy, 11, 5),
+        // fully fills the remaining ask at 11, then posts at 11
+        place(&taker, Side::Buy, 11, 4),
+        // sweeps both bids at 11 and 9, then posts at 9
+        place(&seller, Side::Sell, 9, 10),
+        // cancel the remaining ask at 12
+        Op::Cancel(0),
+        place(&maker, Side::Buy, 7, 3),
+    ];
+    let (a, b) = run_ops(ops);
+    assert!(!a.bids.is_empty() && !a.asks.is_empty());
+    assert_conversions_agree(a, b);
+}
+
+#[test]
+fn empty_conversion() {
+    assert_conversions_agree(VecOrderbook::default(), BTreeOrderbook::default());
+}
+
+proptest! {
+    #[test]
+    fn fuzz_backends_agree((_, order_reqs) in arb_limit_order_vecs(18, 6, 12)) {
+        new_test_context();
+        let mut counter = new_counter();
+        let buyer = AccountId::new_unchecked("buyer.near".to_string());
+        let seller = AccountId::new_unchecked("seller.near".to_string());
+
+        let mut ops = vec![];
+        for (i, mut req) in order_reqs.into_iter().enumerate() {
+            req.sequence_number = counter.next();
+            let user = match req.side {
+                Side::Buy => buyer.clone(),
+                Side::Sell => seller.clone(),
+            };
+            ops.push(Op::Place(user, req));
+            // every third order, cancel the order placed two orders ago, if
+            // it's still open
+            if i % 3 == 2 {
+                ops.push(Op::Cancel(ops.len() - 3));
+            }
+        }
+        let (a, b) = run_ops(ops);
+        assert_conversions_agree(a, b);
+    }
+}
@@ -1,5 +1,6 @@
 pub use crate::*;
 
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use proptest::prelude::*;
 
 use super::test_utils::*;
@@ -65,6 +66,10 @@ fn basic_tvl() {
         order_type: OrderType::Limit,
         client_id: None,
         available_quote_lots: Some(5), // TODO: formulated to exactly lock the correct balance with no refund
+        max_impact_bps: None,
+        max_matches: None,
+        max_price_levels: None,
+        notional_quote_lots: None,
         base_lot_size,
         quote_lot_size,
         base_denomination,
@@ -77,6 +82,10 @@ fn basic_tvl() {
         order_type: OrderType::Limit,
         client_id: None,
         available_quote_lots: None,
+        max_impact_bps: None,
+        max_matches: None,
+        max_price_levels: None,
+        notional_quote_lots: None,
         base_lot_size,
         quote_lot_size,
         base_denomination,
@@ -200,6 +209,10 @@ prop_compose! {
             sequence_number,
             limit_price_lots: Some(limit_price_lots),
             available_quote_lots,
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
             max_qty_lots,
             side,
             order_type,
@@ -211,7 +224,7 @@ prop_compose! {
     }
 }
 
-fn arb_limit_order_vecs(
+pub(crate) fn arb_limit_order_vecs(
     max_base_decimals: u32,
     max_quote_decimals: u32,
     max_orders: usize,
@@ -220,10 +233,10 @@ fn arb_limit_order_vecs(
         move |(base_lot_size, quote_lot_size, base_denomination)| {
             (
                 Just((base_lot_size, quote_lot_size, base_denomination)),
+                // with some decimals, most buys round to zero quantity. these
+                // are kept, and tests check that they're rejected
                 prop::collection::vec(
-                    arb_limit_order_req(base_lot_size, quote_lot_size, base_denomination)
-                        // TODO: too many rejects
-                        .prop_filter("invalid order", |req| req.max_qty_lots > 0),
+                    arb_limit_order_req(base_lot_size, quote_lot_size, base_denomination),
                     1..=max_orders,
                 ),
             )
@@ -231,6 +244,23 @@ fn arb_limit_order_vecs(
     )
 }
 
+/// Place the order, or check that it's rejected if it has no quantity.
+/// Returns [None] if the order was rejected.
+pub(crate) fn place_or_check_rejected<T: L2>(
+    ob: &mut Orderbook<T>,
+    user: &AccountId,
+    req: NewOrder,
+) -> Option<PlaceOrderResult> {
+    if req.max_qty_lots == 0 {
+        let digest = ob.digest();
+        let res = ob.try_place_order(user, req);
+        assert_eq!(res.err(), Some(PlaceOrderError::ZeroQuantity));
+        assert_eq!(ob.digest(), digest, "rejected order modified the book");
+        return None;
+    }
+    Some(ob.place_order(user, req))
+}
+
 fn req_to_string(req: &NewOrder) -> String {
     if req.side == Side::Buy {
         format!(
@@ -254,8 +284,14 @@ fn req_to_string(req: &NewOrder) -> String {
 proptest! {
     #[test]
     fn test_arb_order_req((_, order_reqs) in arb_limit_order_vecs(18, 6, 1)) {
+        new_test_context();
         for req in order_reqs {
-            assert!(req.max_qty_lots > 0, "invalid order")
+            let expected = if req.max_qty_lots > 0 {
+                Ok(())
+            } else {
+                Err(PlaceOrderError::ZeroQuantity)
+            };
+            assert_eq!(req.validate(), expected);
         }
     }
 
@@ -263,6 +299,7 @@ proptest! {
     /// being generated from thin air. Does not account for fees.
     #[test]
     fn fuzz_ob_limit_order_integrity((decimals, order_reqs) in arb_limit_order_vecs(18, 6, 6)) {
+        new_test_context();
         let (base_lot_size, quote_lot_size, base_denomination) = decimals;
 
         let mut ob = new_orderbook();
@@ -271,6 +308,10 @@ proptest! {
         let seller = AccountId::new_unchecked("seller.near".to_string());
 
         for mut req in order_reqs {
+            if req.max_qty_lots == 0 {
+                place_or_check_rejected(&mut ob, &buyer, req);
+                continue;
+            }
             req.assert_valid();
             // set up the sequence number
             // TODO: setting the sequence number here is messy. Better to either:
@@ -285,6 +326,18 @@ proptest! {
 
             let req_clone = req.clone();
 
+            // assert native budget accounting doesn't lose value
+            if let Some(available_quote_lots) = req.available_quote_lots {
+                let pending = ob.match_order(user, &req).unwrap();
+                let paid: u128 = pending.matches.iter().map(|m| m.native_quote_paid).sum();
+                assert_eq!(
+                    paid + pending.unused_quote_native.unwrap(),
+                    available_quote_lots as u128 * quote_lot_size,
+                    "quote budget lost: order {}",
+                    req_to_string(&req)
+                );
+            }
+
             let tvl_before = req.value_locked()
                 + ob.value_locked(base_lot_size, quote_lot_size, base_denomination);
             let result = ob.place_order(user, req);
@@ -299,6 +352,9 @@ proptest! {
                 req_to_string(&req_clone)
             );
 
+            ob.bids.verify_invariants().unwrap();
+            ob.asks.verify_invariants().unwrap();
+
             // assert order doesn't oversell
             assert!(
                 req_clone.max_qty_lots >= result.fill_qty_lots,
@@ -313,4 +369,45 @@ proptest! {
             }
         }
     }
+
+    /// Test that a book survives a Borsh round trip with every order intact,
+    /// including the fields that are skipped when serializing and initialized
+    /// by the L2 on access.
+    #[test]
+    fn fuzz_ob_borsh_round_trip((_, order_reqs) in arb_limit_order_vecs(18, 6, 12)) {
+        new_test_context();
+        let mut ob = new_orderbook();
+        let mut counter = new_counter();
+        let buyer = AccountId::new_unchecked("buyer.near".to_string());
+        let seller = AccountId::new_unchecked("seller.near".to_string());
+
+        for mut req in order_reqs {
+            req.sequence_number = counter.next();
+            let user = match req.side {
+                Side::Buy => &buyer,
+                Side::Sell => &seller
+            };
+            place_or_check_rejected(&mut ob, user, req);
+        }
+
+        let bytes = ob.try_to_vec().unwrap();
+        let restored = VecOrderbook::try_from_slice(&bytes).unwrap();
+
+        for side in [Side::Buy, Side::Sell].iter().copied() {
+            let before: Vec<OpenLimitOrder> = ob.side(side).iter().collect();
+            let after: Vec<OpenLimitOrder> = restored.side(side).iter().collect();
+            assert_eq!(before.len(), after.len(), "order count mismatch");
+            for (b, a) in before.iter().zip(after.iter()) {
+                assert_eq!(a.id(), b.id());
+                assert_eq!(a.owner_id, b.owner_id);
+                assert_eq!(a.open_qty_lots, b.open_qty_lots);
+                assert_eq!(a.client_id, b.client_id);
+                assert_eq!(a.unwrap_price(), b.unwrap_price());
+                assert_eq!(a.unwrap_side(), side);
+                assert_eq!(a.unwrap_price_rank(), b.unwrap_price_rank());
+                assert_eq!(restored.get_order(b.id()).as_ref(), Some(b));
+            }
+        }
+        assert_eq!(restored.try_to_vec().unwrap(), bytes);
+    }
 }
@@ -1,5 +1,8 @@
 /// Unit tests
 pub mod basic;
+pub mod equivalence;
 pub mod fuzz;
+pub mod outcomes;
+pub mod replay;
 pub mod swap_math;
 pub mod test_utils;
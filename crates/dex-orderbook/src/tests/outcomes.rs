@@ -0,0 +1,95 @@
+/// Table-driven tests of order outcomes for every order type.
+use super::test_utils::*;
+
+const TAKER_QTY: LotBalance = 5;
+const PARTIAL_QTY: LotBalance = 3;
+const MAKER_PRICE: LotBalance = 10;
+
+/// Place a resting order on the opposite side, then the taker order, and
+/// return the taker's result.
+fn run(side: Side, order_type: OrderType, crosses: bool, full_liquidity: bool) -> PlaceOrderResult {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    let order = |seq, side, price, qty| NewOrder {
+        limit_price_lots: price,
+        order_type,
+        available_quote_lots: match side {
+            Side::Buy => Some(1_000),
+            Side::Sell => None,
+        },
+        ..new_order(seq, side, 0, qty)
+    };
+
+    // market orders have no price, so they only miss an empty book
+    if crosses || order_type != OrderType::Market {
+        let qty = if full_liquidity {
+            TAKER_QTY
+        } else {
+            PARTIAL_QTY
+        };
+        let maker_side = match side {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+        let resting = NewOrder {
+            order_type: OrderType::Limit,
+            ..order(counter.next(), maker_side, Some(MAKER_PRICE), qty)
+        };
+        ob.place_order(&maker, resting);
+    }
+
+    let price = match (order_type, crosses, side) {
+        (OrderType::Market, _, _) => None,
+        (_, true, _) => Some(MAKER_PRICE),
+        (_, false, Side::Buy) => Some(MAKER_PRICE - 1),
+        (_, false, Side::Sell) => Some(MAKER_PRICE + 1),
+    };
+    ob.place_order(&taker, order(counter.next(), side, price, TAKER_QTY))
+}
+
+#[test]
+fn test_order_type_matrix() {
+    use OrderOutcome::*;
+    use OrderType::*;
+
+    // (order type, crosses, full liquidity) => (outcome, fill qty, open qty)
+    #[rustfmt::skip]
+    let cases = [
+        (Limit,             true,  true,  Filled,      5, 0),
+        (Limit,             true,  false, PartialFill, 3, 2),
+        (Limit,             false, true,  Posted,      0, 5),
+        (Limit,             false, false, Posted,      0, 5),
+        (ImmediateOrCancel, true,  true,  Filled,      5, 0),
+        (ImmediateOrCancel, true,  false, PartialFill, 3, 0),
+        (ImmediateOrCancel, false, true,  Cancelled,   0, 0),
+        (ImmediateOrCancel, false, false, Cancelled,   0, 0),
+        (PostOnly,          true,  true,  Rejected,    0, 0),
+        (PostOnly,          true,  false, Rejected,    0, 0),
+        (PostOnly,          false, true,  Posted,      0, 5),
+        (PostOnly,          false, false, Posted,      0, 5),
+        (FillOrKill,        true,  true,  Filled,      5, 0),
+        (FillOrKill,        true,  false, Rejected,    0, 0),
+        (FillOrKill,        false, true,  Rejected,    0, 0),
+        (FillOrKill,        false, false, Rejected,    0, 0),
+        (Market,            true,  true,  Filled,      5, 0),
+        (Market,            true,  false, PartialFill, 3, 0),
+        (Market,            false, true,  Cancelled,   0, 0),
+        (Market,            false, false, Cancelled,   0, 0),
+    ];
+    assert_eq!(cases.len(), OrderType::all().len() * 4);
+
+    for side in [Side::Buy, Side::Sell].iter().copied() {
+        for (order_type, crosses, full, outcome, fill_qty, open_qty) in cases.iter().copied() {
+            let res = run(side, order_type, crosses, full);
+            let case = format!(
+                "{} {:?}, crosses: {}, full liquidity: {}",
+                side, order_type, crosses, full
+            );
+            assert_eq!(res.outcome, outcome, "{}", case);
+            assert_eq!(res.fill_qty_lots, fill_qty, "{}", case);
+            assert_eq!(res.open_qty_lots, open_qty, "{}", case);
+        }
+    }
+}
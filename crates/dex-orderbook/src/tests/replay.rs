@@ -0,0 +1,208 @@
+use near_sdk::json_types::U128;
+use tonic_sdk_dex_events::*;
+
+use super::test_utils::*;
+
+const BASE_LOT_SIZE: u128 = 10;
+const QUOTE_LOT_SIZE: u128 = 1;
+const BASE_DENOMINATION: u128 = 10;
+
+fn market_id() -> MarketId {
+    MarketId([1; 32])
+}
+
+fn calc() -> OrderbookCalculator {
+    OrderbookCalculator {
+        base_lot_size: BASE_LOT_SIZE,
+        quote_lot_size: QUOTE_LOT_SIZE,
+        base_denomination: BASE_DENOMINATION,
+    }
+}
+
+#[allow(deprecated)]
+fn cancel_event(res: &CancelOrderResult) -> EventType {
+    EventType::Cancel(NewCancelEvent {
+        market_id: market_id(),
+        cancels: vec![CancelEventData {
+            order_id: res.order.id(),
+            limit_price: U128(res.order.unwrap_price() as u128 * QUOTE_LOT_SIZE),
+            refund_amount: U128(0),
+            refund_token: TokenType::NativeNear,
+            cancelled_qty: U128(res.order.open_qty_lots as u128 * BASE_LOT_SIZE),
+            price_rank: 0,
+            best_bid: None,
+            best_ask: None,
+        }],
+    })
+}
+
+/// Round trip an event through its emitted JSON, as an indexer would read it.
+/// Serializes directly instead of reading the logs, so this works with the
+/// `no_emit` feature.
+fn round_trip(event: EventType) -> Event {
+    near_sdk::serde_json::from_str(&Event::new(event).to_string()).unwrap()
+}
+
+/// [new_order] with this module's lot sizes, plus a client ID and a budget for
+/// buys so the emitted events fill in every field.
+fn replay_order(sequence_number: u64, side: Side, price: u64, qty: u64) -> NewOrder {
+    NewOrder {
+        client_id: Some(sequence_number as u32),
+        available_quote_lots: match side {
+            Side::Buy => Some(
+                (get_bid_quote_value(qty, price, BASE_LOT_SIZE, QUOTE_LOT_SIZE, BASE_DENOMINATION)
+                    / QUOTE_LOT_SIZE) as u64,
+            ),
+            Side::Sell => None,
+        },
+        quote_lot_size: QUOTE_LOT_SIZE,
+        base_denomination: BASE_DENOMINATION,
+        base_lot_size: BASE_LOT_SIZE,
+        ..new_order(sequence_number, side, price, qty)
+    }
+}
+
+#[test]
+fn replay_matches_digest() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+
+    let mut emitted: Vec<Event> = vec![];
+    let mut cancel_id = None;
+    for (user, side, price, qty) in [
+        (&maker, Side::Sell, 12, 5),
+        (&maker, Side::Sell, 11, 3),
+        (&maker, Side::Sell, 11, 4),
+        (&maker, Side::Buy, 9, 6),
+        (&maker, Side::Buy, 8, 2),
+        // fully fills the first ask at 11, partially fills the second
+        (&taker, Side::Buy, 11, 5),
+        // fully fills the remaining ask at 11, then posts at 11
+        (&taker, Side::Buy, 11, 4),
+        // partially fills the bid at 11
+        (&maker, Side::Sell, 10, 1),
+        (&taker, Side::Sell, 13, 7),
+    ] {
+        let order = replay_order(counter.next(), side, price, qty);
+        let (res, events) = ob.place_order_and_events(user, order, market_id(), &calc());
+        emitted.extend(events.into_iter().map(round_trip));
+        if price == 8 {
+            cancel_id = Some(res.id);
+        }
+    }
+    let cancel = ob.cancel_order(cancel_id.unwrap()).unwrap();
+    emitted.push(round_trip(cancel_event(&cancel)));
+
+    let replayed = VecOrderbook::replay(&emitted, BASE_LOT_SIZE);
+
+    assert_ne!(
+        ob.digest(),
+        new_orderbook().digest(),
+        "book should not be empty"
+    );
+    assert_eq!(replayed.digest(), ob.digest(), "replayed book differs");
+    assert_eq!(replayed, ob);
+    // the taker's posted bid, partially filled by the last maker ask
+    let best_bid = replayed.find_bbo(Side::Buy).unwrap();
+    assert_eq!(best_bid.unwrap_price(), 11);
+    assert_eq!(best_bid.open_qty_lots, 1);
+}
+
+#[test]
+fn replay_modify() {
+    let user = AccountId::new_unchecked("maker".to_string());
+    let mut ob = VecOrderbook::from_orders(&user, vec![replay_order(1, Side::Sell, 10, 2)]);
+    let old_order_id = new_order_id(Side::Sell, 10, 1);
+    let new_order_id = new_order_id(Side::Sell, 12, 2);
+    ob.apply_event(
+        &Event::new(EventType::Modify(NewModifyEvent {
+            market_id: market_id(),
+            old_order_id,
+            new_order_id,
+            old_qty: U128(2 * BASE_LOT_SIZE),
+            new_qty: U128(3 * BASE_LOT_SIZE),
+            new_price: U128(12 * QUOTE_LOT_SIZE),
+        })),
+        BASE_LOT_SIZE,
+    );
+
+    assert!(ob.get_order(old_order_id).is_none());
+    let order = ob.get_order(new_order_id).unwrap();
+    assert_eq!(order.owner_id, user);
+    assert_eq!(order.open_qty_lots, 3);
+    assert_eq!(order.client_id, Some(1));
+    assert_eq!(ob.find_bbo(Side::Sell).unwrap().id(), new_order_id);
+}
+
+#[test]
+fn digest_depends_on_quantity() {
+    let user = AccountId::new_unchecked("maker".to_string());
+    let a = VecOrderbook::from_orders(&user, vec![replay_order(1, Side::Sell, 10, 2)]);
+    let b = VecOrderbook::from_orders(&user, vec![replay_order(1, Side::Sell, 10, 3)]);
+    assert_ne!(a.digest(), b.digest());
+    assert_eq!(
+        a.digest(),
+        VecOrderbook::from_orders(&user, vec![replay_order(1, Side::Sell, 10, 2)]).digest()
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn place_order_and_events_match_result() {
+    let mut counter = new_counter();
+    let mut ob = new_orderbook();
+    let maker = AccountId::new_unchecked("maker".to_string());
+    let taker = AccountId::new_unchecked("taker".to_string());
+    ob.place_order(&maker, replay_order(counter.next(), Side::Sell, 11, 3));
+    ob.place_order(&maker, replay_order(counter.next(), Side::Sell, 12, 3));
+
+    // no fills, only an order event
+    let (_, events) = ob.place_order_and_events(
+        &taker,
+        replay_order(counter.next(), Side::Buy, 9, 1),
+        market_id(),
+        &calc(),
+    );
+    assert_eq!(events.len(), 1);
+
+    let order = replay_order(counter.next(), Side::Buy, 12, 4);
+    let (res, events) = ob.place_order_and_events(&taker, order.clone(), market_id(), &calc());
+    assert_eq!(events.len(), 2);
+    match &events[0] {
+        EventType::Order(ev) => {
+            assert_eq!(ev.order_id, res.id);
+            assert_eq!(ev.account_id, taker);
+            assert_eq!(ev.market_id, market_id());
+            assert_eq!(ev.quantity, U128(4 * BASE_LOT_SIZE));
+            assert_eq!(ev.limit_price, U128(12 * QUOTE_LOT_SIZE));
+            assert_eq!(ev.open_quantity, Some(U128(0)));
+            assert_eq!(ev.client_id, order.client_id);
+        }
+        _ => panic!("expected order event"),
+    }
+    match &events[1] {
+        EventType::Fill(ev) => {
+            assert_eq!(ev.order_id, res.id);
+            assert_eq!(ev.fills.len(), res.matches.len());
+            for (fill, m) in ev.fills.iter().zip(res.matches.iter()) {
+                assert_eq!(fill.maker_order_id, m.maker_order_id);
+                assert_eq!(fill.maker_account_id, maker);
+                assert_eq!(fill.taker_account_id, taker);
+                assert_eq!(fill.fill_qty, U128(m.native_base_filled));
+                assert_eq!(fill.quote_qty, U128(m.native_quote_paid));
+                assert_eq!(
+                    fill.fill_price,
+                    U128(m.fill_price_lots as u128 * QUOTE_LOT_SIZE)
+                );
+                assert_eq!(fill.side, Side::Buy);
+                assert_eq!(fill.maker_price_rank, m.maker_order_price_rank);
+            }
+            // the ask at 12 was one level behind the ask at 11 when matched
+            let ranks: Vec<u32> = ev.fills.iter().map(|f| f.maker_price_rank).collect();
+            assert_eq!(ranks, vec![0, 1]);
+        }
+        _ => panic!("expected fill event"),
+    }
+}
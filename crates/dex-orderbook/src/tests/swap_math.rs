@@ -23,6 +23,10 @@ fn swap_math_bug() {
         limit_price_lots: Some(480),
         max_qty_lots: 998, // based on fill event, order only had this much left at time of swap
         available_quote_lots: None,
+        max_impact_bps: None,
+        max_matches: None,
+        max_price_levels: None,
+        notional_quote_lots: None,
 
         quote_lot_size,
         base_denomination,
@@ -38,6 +42,10 @@ fn swap_math_bug() {
         limit_price_lots: Some(488),
         max_qty_lots: 8568,
         available_quote_lots: None,
+        max_impact_bps: None,
+        max_matches: None,
+        max_price_levels: None,
+        notional_quote_lots: None,
 
         quote_lot_size,
         base_denomination,
@@ -64,6 +72,10 @@ fn swap_math_bug() {
             limit_price_lots: None,
             max_qty_lots: u64::MAX,
             available_quote_lots: Some(4795), // 4.80 - 0.1% is 4.7952, last 2 is dropped due to lots
+            max_impact_bps: None,
+            max_matches: None,
+            max_price_levels: None,
+            notional_quote_lots: None,
 
             quote_lot_size,
             base_denomination,
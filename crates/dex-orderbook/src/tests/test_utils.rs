@@ -3,12 +3,6 @@ pub use tonic_sdk_dex_types::*;
 
 pub use crate::*;
 
-pub fn add_orders(ob: &mut VecOrderbook, orders: Vec<NewOrder>) {
-    for (_, order) in orders.into_iter().enumerate() {
-        ob.place_order(&AccountId::new_unchecked("test_user".to_string()), order);
-    }
-}
-
 pub fn new_orderbook() -> VecOrderbook {
     VecOrderbook::default()
 }
@@ -18,18 +12,39 @@ pub fn place_order(ob: &mut VecOrderbook, account_id: &AccountId, order: NewOrde
     res.id
 }
 
-#[derive(Default)]
-pub struct Counter {
-    pub prev: u64,
+pub fn new_counter() -> SequenceCounter {
+    SequenceCounter::default()
 }
 
-impl Counter {
-    pub fn next(&mut self) -> u64 {
-        self.prev += 1;
-        self.prev
+/// Limit order with no quote budget and lot sizes of 1. Tests override the
+/// fields they exercise with struct update syntax.
+pub fn new_order(
+    sequence_number: SequenceNumber,
+    side: Side,
+    price_lots: LotBalance,
+    qty_lots: LotBalance,
+) -> NewOrder {
+    NewOrder {
+        sequence_number,
+        limit_price_lots: Some(price_lots),
+        max_qty_lots: qty_lots,
+        side,
+        order_type: OrderType::Limit,
+        client_id: None,
+        available_quote_lots: None,
+        max_impact_bps: None,
+        max_matches: None,
+        max_price_levels: None,
+        notional_quote_lots: None,
+        quote_lot_size: 1,
+        base_denomination: 1,
+        base_lot_size: 1,
     }
 }
 
-pub fn new_counter() -> Counter {
-    Counter::default()
+/// Start a fresh mocked chain context. Proptest runs every case of a test on
+/// one thread, so fuzz cases call this first to keep logs, eg, the
+/// `debug_log` match trace, from accumulating across cases.
+pub fn new_test_context() {
+    near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new().build());
 }
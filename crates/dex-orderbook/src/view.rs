@@ -0,0 +1,190 @@
+/// Aggregated snapshots of the book and diffs between them.
+use std::cmp::Ordering;
+
+use near_sdk::serde::{Deserialize, Serialize};
+use tonic_sdk_dex_types::{LotBalance, Side};
+
+use crate::*;
+
+/// Total open quantity resting at one price.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct L2Level {
+    pub price_lots: LotBalance,
+    pub open_qty_lots: LotBalance,
+}
+
+/// Snapshot of the top price levels of each side, best price first. Made with
+/// [view](Orderbook::view).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OrderbookView {
+    pub bids: Vec<L2Level>,
+    pub asks: Vec<L2Level>,
+}
+
+/// The top price levels of each side with the orders resting at each, best
+/// price first. Made with [levels](Orderbook::levels).
+///
+/// Iterating yields `(side, price, orders)` for each bid level, best price
+/// first, followed by each ask level, best price first. Orders are in time
+/// priority.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OrderbookLevels {
+    pub bids: Vec<(LotBalance, Vec<OpenLimitOrder>)>,
+    pub asks: Vec<(LotBalance, Vec<OpenLimitOrder>)>,
+}
+
+/// Change to a single price level between two snapshots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum LevelChange {
+    Added(L2Level),
+    Removed(L2Level),
+    Changed {
+        price_lots: LotBalance,
+        old_qty_lots: LotBalance,
+        new_qty_lots: LotBalance,
+    },
+}
+
+/// Per-side level changes between two [OrderbookView]s, best price first.
+/// Unchanged levels are omitted.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OrderbookDiff {
+    pub bids: Vec<LevelChange>,
+    pub asks: Vec<LevelChange>,
+}
+
+impl OrderbookDiff {
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+}
+
+impl OrderbookView {
+    /// Level changes needed to turn `self` into `other`.
+    ///
+    /// Both views must list levels best price first, as produced by
+    /// [view](Orderbook::view). Levels beyond the depth of either snapshot
+    /// show up as added or removed.
+    pub fn diff(&self, other: &OrderbookView) -> OrderbookDiff {
+        OrderbookDiff {
+            bids: diff_levels(&self.bids, &other.bids, Side::Buy),
+            asks: diff_levels(&self.asks, &other.asks, Side::Sell),
+        }
+    }
+}
+
+impl IntoIterator for OrderbookLevels {
+    type Item = (Side, LotBalance, Vec<OpenLimitOrder>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let bids = self.bids.into_iter().map(|(p, o)| (Side::Buy, p, o));
+        let asks = self.asks.into_iter().map(|(p, o)| (Side::Sell, p, o));
+        bids.chain(asks).collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl<T: L2> Orderbook<T> {
+    /// Aggregate the top `depth` price levels of each side.
+    pub fn view(&self, depth: usize) -> OrderbookView {
+        let levels = |l2: &T| -> Vec<L2Level> {
+            l2.take_depth(depth)
+                .into_iter()
+                .map(|(price_lots, orders)| L2Level {
+                    price_lots,
+                    open_qty_lots: orders.iter().map(|o| o.open_qty_lots).sum(),
+                })
+                .collect()
+        };
+        OrderbookView {
+            bids: levels(&self.bids),
+            asks: levels(&self.asks),
+        }
+    }
+
+    /// The top `depth` price levels of each side with their orders.
+    pub fn levels(&self, depth: usize) -> OrderbookLevels {
+        OrderbookLevels {
+            bids: self.bids.take_depth(depth),
+            asks: self.asks.take_depth(depth),
+        }
+    }
+
+    /// One page of a side of the book: `count` price levels starting at
+    /// `start_level`, best price first, where level 0 is the best price. The
+    /// other side of the returned view is empty. Page through a deep book by
+    /// advancing `start_level` by `count` until a page comes back short.
+    pub fn view_from_offset(&self, side: Side, start_level: usize, count: usize) -> OrderbookView {
+        let mut levels: Vec<L2Level> = vec![];
+        let mut level_idx = 0;
+        let mut prev_price: Option<LotBalance> = None;
+        for order in self.side(side).iter() {
+            let price_lots = order.unwrap_price();
+            if prev_price.is_some() && prev_price != Some(price_lots) {
+                level_idx += 1;
+            }
+            prev_price = Some(price_lots);
+            if level_idx < start_level {
+                continue;
+            }
+            if level_idx >= start_level + count {
+                break;
+            }
+            if levels.last().map(|l| l.price_lots) != Some(price_lots) {
+                levels.push(L2Level {
+                    price_lots,
+                    open_qty_lots: 0,
+                });
+            }
+            levels.last_mut().unwrap().open_qty_lots += order.open_qty_lots;
+        }
+
+        let mut view = OrderbookView::default();
+        match side {
+            Side::Buy => view.bids = levels,
+            Side::Sell => view.asks = levels,
+        }
+        view
+    }
+}
+
+/// Merge two level lists sorted best price first, emitting a change for each
+/// price that was added, removed, or had its quantity change.
+fn diff_levels(before: &[L2Level], after: &[L2Level], side: Side) -> Vec<LevelChange> {
+    // order in which prices appear on the side, ie, descending for bids
+    let cmp = |a: LotBalance, b: LotBalance| match side {
+        Side::Buy => b.cmp(&a),
+        Side::Sell => a.cmp(&b),
+    };
+
+    let mut changes = vec![];
+    let mut before = before.iter().peekable();
+    let mut after = after.iter().peekable();
+    loop {
+        let ordering = match (before.peek(), after.peek()) {
+            (Some(b), Some(a)) => cmp(b.price_lots, a.price_lots),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        match ordering {
+            Ordering::Less => changes.push(LevelChange::Removed(*before.next().unwrap())),
+            Ordering::Greater => changes.push(LevelChange::Added(*after.next().unwrap())),
+            Ordering::Equal => {
+                let (b, a) = (before.next().unwrap(), after.next().unwrap());
+                if b.open_qty_lots != a.open_qty_lots {
+                    changes.push(LevelChange::Changed {
+                        price_lots: b.price_lots,
+                        old_qty_lots: b.open_qty_lots,
+                        new_qty_lots: a.open_qty_lots,
+                    });
+                }
+            }
+        }
+    }
+    changes
+}
@@ -1,20 +1,32 @@
+//! Without the default `near` feature, only the order ID and 256-bit math is
+//! compiled and the crate is `no_std`, eg, for off-chain pricing in wasm.
+#![cfg_attr(not(any(feature = "near", test)), no_std)]
+
+#[cfg(feature = "near")]
 pub mod market_id;
+pub mod math;
 pub mod order_id;
 pub mod order_type;
 pub mod side;
+#[cfg(feature = "near")]
 pub mod token_type;
+mod u256;
 
+#[cfg(feature = "near")]
 pub use market_id::*;
+pub use math::*;
 pub use order_id::*;
 pub use order_type::*;
 pub use side::*;
+#[cfg(feature = "near")]
 pub use token_type::*;
-
-uint::construct_uint! {
-    pub struct U256(4);
-}
+pub use u256::U256;
 
 /// Sequence number is capped at 2^63. At 50k TPS, each placing 100 batch
 /// orders, this would be around 58k years of order IDs.
 pub type SequenceNumber = u64;
+
+/// Largest sequence number that fits in an [OrderId] without clobbering the
+/// side bit.
+pub const SEQUENCE_NUMBER_MAX: SequenceNumber = u64::MAX / 2;
 pub type LotBalance = u64;
@@ -1,22 +1,46 @@
 use std::convert::TryFrom;
 use std::fmt::Display;
 use std::ops::Deref;
+use std::str::FromStr;
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, AccountId};
 
 use tonic_sdk_json::{impl_base58_serde, Base58VecU8};
 
+use tonic_sdk_dex_errors as errors;
+
+use crate::TokenType;
+
 /// Market IDs are sha256 hashes (ie 32 byte arrays)
 #[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Clone, Copy, BorshDeserialize, BorshSerialize)]
 pub struct MarketId(pub [u8; 32]);
 
 impl MarketId {
+    /// Build an ID from the first 32 bytes of `data`. Panics if there are
+    /// fewer than 32 bytes, and ignores any past the first 32. Use
+    /// [try_new](MarketId::try_new) for untrusted input.
     pub fn new_unchecked(data: &[u8]) -> Self {
         let mut buf: [u8; 32] = Default::default();
         buf.copy_from_slice(&data[..32]);
         Self(buf)
     }
+
+    /// Build an ID from exactly 32 bytes. Returns
+    /// [INVALID_MARKET_ID](errors::INVALID_MARKET_ID) for any other length.
+    pub fn try_new(data: &[u8]) -> Result<Self, &'static str> {
+        <[u8; 32]>::try_from(data)
+            .map(Self)
+            .map_err(|_| errors::INVALID_MARKET_ID)
+    }
+
+    /// Derive the ID of a market from its creator and tokens. The base and
+    /// quote tokens are ordered, ie, swapping them produces a different ID.
+    pub fn from_parts(creator_id: &AccountId, base: &TokenType, quote: &TokenType) -> Self {
+        let preimage = format!("{}:{}:{}", creator_id, base.key(), quote.key());
+        Self::new_unchecked(&env::sha256(preimage.as_bytes()))
+    }
 }
 
 impl_base58_serde!(MarketId);
@@ -25,11 +49,7 @@ impl TryFrom<&Vec<u8>> for MarketId {
     type Error = ();
 
     fn try_from(d: &Vec<u8>) -> Result<Self, Self::Error> {
-        if d.len() != 32 {
-            Err(())
-        } else {
-            Ok(MarketId::new_unchecked(d))
-        }
+        MarketId::try_new(d).map_err(|_| ())
     }
 }
 
@@ -43,6 +63,21 @@ impl Display for MarketId {
     }
 }
 
+/// Parse either the bare base58 form or the `MarketId<...>` form produced by
+/// [Display].
+impl FromStr for MarketId {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let encoded = s
+            .strip_prefix("MarketId<")
+            .and_then(|s| s.strip_suffix('>'))
+            .unwrap_or(s);
+        let data = near_sdk::bs58::decode(encoded).into_vec().map_err(|_| ())?;
+        MarketId::try_from(&data)
+    }
+}
+
 impl From<Base58VecU8> for MarketId {
     fn from(b: Base58VecU8) -> Self {
         MarketId::try_from(&b.0).expect("malformed market ID")
@@ -74,3 +109,62 @@ impl Deref for MarketId {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn try_new() {
+        let bytes: Vec<u8> = (0..33).collect();
+        let id = MarketId::try_new(&bytes[..32]).unwrap();
+        assert_eq!(id, MarketId::new_unchecked(&bytes[..32]));
+        assert_eq!(
+            MarketId::try_new(&bytes[..31]),
+            Err(errors::INVALID_MARKET_ID)
+        );
+        assert_eq!(MarketId::try_new(&bytes), Err(errors::INVALID_MARKET_ID));
+        assert_eq!(MarketId::try_new(&[]), Err(errors::INVALID_MARKET_ID));
+    }
+
+    #[test]
+    fn test_market_id_display_round_trip() {
+        let market_id = MarketId([7; 32]);
+        let parsed: MarketId = market_id.to_string().parse().unwrap();
+        assert_eq!(market_id, parsed);
+    }
+
+    #[test]
+    fn test_market_id_bare_round_trip() {
+        let market_id = MarketId([7; 32]);
+        let encoded = near_sdk::bs58::encode(&market_id.0).into_string();
+        let parsed: MarketId = encoded.parse().unwrap();
+        assert_eq!(market_id, parsed);
+    }
+
+    #[test]
+    fn test_market_id_parse_wrong_length() {
+        let encoded = near_sdk::bs58::encode(&[7u8; 31]).into_string();
+        assert_eq!(MarketId::from_str(&encoded), Err(()));
+        assert_eq!(
+            MarketId::from_str(&format!("MarketId<{}>", encoded)),
+            Err(())
+        );
+        assert_eq!(MarketId::from_str("MarketId<not base58!>"), Err(()));
+    }
+
+    #[test]
+    fn test_market_id_from_parts() {
+        let creator: AccountId = "creator.near".parse().unwrap();
+        let base = TokenType::NativeNear;
+        let quote = TokenType::from_key("ft:usdc.near");
+
+        let market_id = MarketId::from_parts(&creator, &base, &quote);
+        assert_eq!(market_id, MarketId::from_parts(&creator, &base, &quote));
+        assert_ne!(market_id, MarketId::from_parts(&creator, &quote, &base));
+        assert_ne!(
+            market_id,
+            MarketId::from_parts(&"other.near".parse().unwrap(), &base, &quote)
+        );
+    }
+}
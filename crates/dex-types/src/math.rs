@@ -0,0 +1,123 @@
+/// Pure integer math shared by on-chain and off-chain code. Compiles without
+/// `near_sdk`.
+use crate::{LotBalance, U256};
+
+pub const BPS_DIVISOR: u128 = 10_000;
+
+#[macro_export]
+macro_rules! BN {
+    ($v:expr) => {
+        $crate::BN($crate::U256::from($v))
+    };
+}
+
+pub struct BN(pub U256);
+
+impl BN {
+    pub fn mul(&self, v: u128) -> BN {
+        BN(self.0 * U256::from(v))
+    }
+
+    pub fn div(&self, v: u128) -> BN {
+        BN(self.0 / U256::from(v))
+    }
+
+    pub fn add(&self, v: u128) -> BN {
+        BN(self.0 + U256::from(v))
+    }
+
+    pub fn sub(&self, v: u128) -> BN {
+        BN(self.0 - U256::from(v))
+    }
+
+    pub fn as_u128(&self) -> u128 {
+        self.0.as_u128()
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0.as_u64()
+    }
+
+    pub fn add_bps(&self, bps: u16) -> BN {
+        BN(self.0).mul(BPS_DIVISOR + bps as u128).div(BPS_DIVISOR)
+    }
+
+    pub fn sub_bps(&self, bps: u16) -> BN {
+        debug_assert!(bps as u128 <= BPS_DIVISOR, "bps exceeds 100%");
+        BN(self.0).mul(BPS_DIVISOR - bps as u128).div(BPS_DIVISOR)
+    }
+
+    /// Same as [add_bps](BN::add_bps), but returns [None] if the intermediate
+    /// product overflows.
+    pub fn checked_add_bps(&self, bps: u16) -> Option<BN> {
+        self.0
+            .checked_mul(U256::from(BPS_DIVISOR + bps as u128))
+            .map(|v| BN(v / U256::from(BPS_DIVISOR)))
+    }
+
+    /// Same as [sub_bps](BN::sub_bps), but returns [None] if `bps` is greater
+    /// than [BPS_DIVISOR], ie, more than 100%.
+    pub fn checked_sub_bps(&self, bps: u16) -> Option<BN> {
+        let factor = BPS_DIVISOR.checked_sub(bps as u128)?;
+        Some(BN(self.0 * U256::from(factor) / U256::from(BPS_DIVISOR)))
+    }
+}
+
+/// Get the value of a bid in terms of native quote token.
+///
+/// Conceptually, this is price * quantity.
+pub fn get_bid_quote_value(
+    quantity: LotBalance,
+    price: LotBalance,
+    base_lot_size: u128,
+    quote_lot_size: u128,
+    base_denomination: u128,
+) -> u128 {
+    BN!(quantity)
+        .mul(base_lot_size)
+        .mul(price as u128)
+        .mul(quote_lot_size)
+        .div(base_denomination)
+        .as_u128()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_add_bps() {
+        assert_eq!(BN!(1_000).checked_add_bps(0).unwrap().as_u128(), 1_000);
+        assert_eq!(BN!(1_000).checked_add_bps(10_000).unwrap().as_u128(), 2_000);
+        assert_eq!(BN!(1_000).checked_add_bps(15_000).unwrap().as_u128(), 2_500);
+        assert!(BN(U256::MAX).checked_add_bps(1).is_none());
+    }
+
+    #[test]
+    fn checked_sub_bps() {
+        assert_eq!(BN!(1_000).checked_sub_bps(0).unwrap().as_u128(), 1_000);
+        assert_eq!(BN!(1_000).checked_sub_bps(10_000).unwrap().as_u128(), 0);
+        assert!(BN!(1_000).checked_sub_bps(10_001).is_none());
+    }
+
+    #[test]
+    fn bid_quote_value() {
+        // 3 lots of 0.1 base at 25 quote lots of 0.01 per whole base
+        assert_eq!(get_bid_quote_value(3, 25, 100, 10, 1_000), 75);
+        // rounds down
+        assert_eq!(get_bid_quote_value(1, 1, 1, 1, 3), 0);
+    }
+
+    #[test]
+    fn sub_bps() {
+        assert_eq!(BN!(1_000).sub_bps(0).as_u128(), 1_000);
+        assert_eq!(BN!(1_000).sub_bps(2_500).as_u128(), 750);
+        assert_eq!(BN!(1_000).sub_bps(10_000).as_u128(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "bps exceeds 100%")]
+    fn sub_bps_over_100_percent() {
+        BN!(1_000).sub_bps(10_001);
+    }
+}
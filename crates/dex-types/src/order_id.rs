@@ -1,8 +1,12 @@
 /// Implements Order IDs
-use std::convert::TryInto;
+#[cfg(feature = "near")]
+use core::convert::TryInto;
 
+#[cfg(feature = "near")]
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+#[cfg(feature = "near")]
 use near_sdk::serde::{Deserialize, Serialize};
+#[cfg(feature = "near")]
 use tonic_sdk_json::{impl_base58_serde, Base58VecU8};
 
 use crate::*;
@@ -12,7 +16,12 @@ use crate::*;
 /// [ Side | Sequence number | Price in lots ]
 ///    |     63 bits           64 bits
 ///    1 bit
-#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Clone, Copy, BorshDeserialize, BorshSerialize)]
+///
+/// The derived [PartialOrd] compares the packed value, ie, side, then sequence
+/// number, then price. That is *not* price-time priority; sort by
+/// [priority_key](OrderId::priority_key) instead.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Clone, Copy)]
+#[cfg_attr(feature = "near", derive(BorshDeserialize, BorshSerialize))]
 pub struct OrderId(u128);
 
 impl OrderId {
@@ -20,22 +29,37 @@ impl OrderId {
     pub fn into_parts(self) -> (Side, u64, u64) {
         get_order_id_parts(self)
     }
+
+    /// Key that sorts orders on the same side of the book by price-time
+    /// priority: best price first (highest bid, lowest ask), then oldest.
+    /// Keys from different sides aren't comparable.
+    pub fn priority_key(&self) -> (LotBalance, SequenceNumber) {
+        let (side, price, sequence_number) = get_order_id_parts(*self);
+        match side {
+            Side::Buy => (!price, sequence_number),
+            Side::Sell => (price, sequence_number),
+        }
+    }
 }
 
+#[cfg(feature = "near")]
 impl_base58_serde!(OrderId);
 
+#[cfg(feature = "near")]
 impl From<OrderId> for Base58VecU8 {
     fn from(oid: OrderId) -> Self {
         oid.0.to_be_bytes().to_vec().into()
     }
 }
 
+#[cfg(feature = "near")]
 impl From<&OrderId> for Base58VecU8 {
     fn from(oid: &OrderId) -> Self {
         oid.0.to_be_bytes().to_vec().into()
     }
 }
 
+#[cfg(feature = "near")]
 impl From<Base58VecU8> for OrderId {
     fn from(bytes: Base58VecU8) -> Self {
         OrderId(u128::from_be_bytes(bytes.0.try_into().unwrap()))
@@ -75,11 +99,9 @@ mod test {
 
     use proptest::prelude::*;
 
-    const SEQUENCE_NUMBER_MAX: u64 = std::u64::MAX / 2;
-
     proptest! {
         #[test]
-        fn test_order_id(side: Side, price in 1..std::u64::MAX, sequence_number in 1..SEQUENCE_NUMBER_MAX) {
+        fn test_order_id(side: Side, price in 1..u64::MAX, sequence_number in 1..SEQUENCE_NUMBER_MAX) {
             let order_id = new_order_id(side, price, sequence_number);
             let (s, p, sn) = get_order_id_parts(order_id);
 
@@ -116,4 +138,24 @@ mod test {
         assert_eq!(price, p, "Wrong price");
         assert_eq!(sequence_number, sn, "Wrong sequence number");
     }
+
+    #[test]
+    fn test_priority_key() {
+        // raw ordering is by sequence number before price
+        let ask_old_high = new_order_id(Side::Sell, 11, 1);
+        let ask_new_low = new_order_id(Side::Sell, 10, 2);
+        let ask_newer_low = new_order_id(Side::Sell, 10, 3);
+        assert!(ask_old_high < ask_new_low);
+
+        let mut asks = vec![ask_old_high, ask_newer_low, ask_new_low];
+        asks.sort_by_key(|id| id.priority_key());
+        assert_eq!(asks, vec![ask_new_low, ask_newer_low, ask_old_high]);
+
+        let bid_old_low = new_order_id(Side::Buy, 10, 1);
+        let bid_new_high = new_order_id(Side::Buy, 11, 2);
+        let bid_newer_high = new_order_id(Side::Buy, 11, 3);
+        let mut bids = vec![bid_newer_high, bid_old_low, bid_new_high];
+        bids.sort_by_key(|id| id.priority_key());
+        assert_eq!(bids, vec![bid_new_high, bid_newer_high, bid_old_low]);
+    }
 }
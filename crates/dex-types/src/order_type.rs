@@ -1,7 +1,12 @@
+#[cfg(feature = "near")]
 use near_sdk::serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
-#[serde(crate = "near_sdk::serde")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "near",
+    derive(Serialize, Deserialize),
+    serde(crate = "near_sdk::serde")
+)]
 pub enum OrderType {
     /// Order fills at the specified price or better. Any part of the order not
     /// immediately filled will be posted.
@@ -21,18 +26,66 @@ pub enum OrderType {
     ///
     /// Slippage tolerance can be controlled by setting `max_spend`, eg, if the
     /// mid-market price is 10 USDC, place an order to buy 10 NEAR with a
-    /// slippage tolerance of 5% with:
+    /// slippage tolerance of 5% with (see `tonic_sdk_dex_orderbook::NewOrderParams`):
     ///
     /// ```ignore
+    /// let near_denomination: Balance = 10u128.pow(24);
     /// let usdc_denomination: Balance = 1_000_000;
     /// let params = NewOrderParams {
     ///   limit_price: None,
     ///   max_spend: Some(U128(105 * usdc_denomination)),
-    ///   quantity: U128(10),
+    ///   quantity: U128(10 * near_denomination),
     ///   side: Side::Buy,
     ///   order_type: OrderType::Market,
-    ///   ..
-    /// }
+    ///   client_id: None,
+    ///   max_impact_bps: None,
+    /// };
     /// ```
     Market,
 }
+
+impl OrderType {
+    /// Every order type, eg, for listing them in a UI.
+    pub fn all() -> [OrderType; 5] {
+        [
+            Self::Limit,
+            Self::ImmediateOrCancel,
+            Self::PostOnly,
+            Self::FillOrKill,
+            Self::Market,
+        ]
+    }
+
+    /// Short human-readable explanation of the order type.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Limit => "Fills at the specified price or better. Any part not immediately filled is posted.",
+            Self::ImmediateOrCancel => "Fills as much as can be immediately filled and cancels the remainder.",
+            Self::PostOnly => "Posts to the book. If any part would immediately fill, the order is cancelled completely.",
+            Self::FillOrKill => "Immediately fills the whole order or cancels it completely.",
+            Self::Market => "Fills as much as possible at the market price and refunds unused funds.",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_variants_described() {
+        let all = OrderType::all();
+        for order_type in all {
+            // fails to compile if a variant is added without updating `all`
+            let index = match order_type {
+                OrderType::Limit => 0,
+                OrderType::ImmediateOrCancel => 1,
+                OrderType::PostOnly => 2,
+                OrderType::FillOrKill => 3,
+                OrderType::Market => 4,
+            };
+            assert_eq!(all[index], order_type);
+            assert!(!order_type.description().is_empty());
+        }
+    }
+}
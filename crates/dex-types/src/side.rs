@@ -1,3 +1,4 @@
+#[cfg(feature = "near")]
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
     serde::{Deserialize, Serialize},
@@ -6,10 +7,12 @@ use near_sdk::{
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 
-#[derive(
-    Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "near",
+    derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize),
+    serde(crate = "near_sdk::serde")
 )]
-#[serde(crate = "near_sdk::serde")]
 #[cfg_attr(test, derive(Arbitrary))]
 #[repr(u8)]
 pub enum Side {
@@ -17,8 +20,8 @@ pub enum Side {
     Sell,
 }
 
-impl std::fmt::Display for Side {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Side {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::Buy => write!(f, "buy"),
             Self::Sell => write!(f, "sell"),
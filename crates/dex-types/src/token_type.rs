@@ -1,15 +1,19 @@
 use std::convert::TryFrom;
+use std::fmt;
 
 /// Implements structs representing token types supported on the Tonic CLOB.
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{env, AccountId};
+use tonic_sdk_dex_errors as errors;
 
 // TODO: Once MFT standard impl is merged, remove this and use
 // `near_contract_standards::multi_token::token::TokenId`
 pub type TokenId = String;
 
-#[derive(Eq, Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(
+    Eq, Clone, Debug, PartialEq, Hash, BorshDeserialize, BorshSerialize, Serialize, Deserialize,
+)]
 #[serde(crate = "near_sdk::serde")]
 #[serde(tag = "type")]
 pub enum TokenType {
@@ -38,17 +42,18 @@ impl From<&AccountId> for TokenType {
     }
 }
 
-impl ToString for TokenType {
-    fn to_string(&self) -> String {
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TokenType::NativeNear => "NativeNear".to_string(),
+            TokenType::NativeNear => write!(f, "NativeNear"),
             TokenType::FungibleToken { account_id } => {
-                format!("FungibleToken{{account_id: {}}}", account_id)
+                write!(f, "FungibleToken{{account_id: {}}}", account_id)
             }
             TokenType::MultiFungibleToken {
                 account_id,
                 subtoken_id,
-            } => format!(
+            } => write!(
+                f,
                 "MultiFungibleToken{{account_id: {}, subtoken_id: {}}}",
                 account_id, subtoken_id
             ),
@@ -87,7 +92,114 @@ impl TokenType {
         }
     }
 
+    /// Check that the token's account ID is well formed. Account IDs built
+    /// with [AccountId::new_unchecked] or deserialized from borsh aren't
+    /// validated, so call this before using the token in a market.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self {
+            TokenType::NativeNear => Ok(()),
+            TokenType::FungibleToken { account_id }
+            | TokenType::MultiFungibleToken { account_id, .. } => {
+                validate_account_id(account_id.as_str())
+            }
+        }
+    }
+
     pub fn from_account_id(account_id: AccountId) -> TokenType {
         TokenType::FungibleToken { account_id }
     }
+
+    /// Return true if transferring the token requires a call to the token
+    /// contract. Native NEAR is sent with a transfer action instead.
+    pub fn requires_ft_transfer_call(&self) -> bool {
+        match self {
+            TokenType::NativeNear => false,
+            TokenType::FungibleToken { .. } | TokenType::MultiFungibleToken { .. } => true,
+        }
+    }
+}
+
+fn validate_account_id(account_id: &str) -> Result<(), &'static str> {
+    account_id
+        .parse::<AccountId>()
+        .map(|_| ())
+        .map_err(|_| errors::INVALID_TOKEN_ID)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_requires_ft_transfer_call() {
+        let account_id = AccountId::new_unchecked("token.near".to_string());
+
+        assert!(!TokenType::NativeNear.requires_ft_transfer_call());
+        assert!(TokenType::from_account_id(account_id.clone()).requires_ft_transfer_call());
+        assert!(TokenType::MultiFungibleToken {
+            account_id,
+            subtoken_id: "1".to_string(),
+        }
+        .requires_ft_transfer_call());
+    }
+
+    #[test]
+    fn test_hash_map_key() {
+        use std::collections::HashMap;
+
+        let account_id = AccountId::new_unchecked("token.near".to_string());
+        let tokens = [
+            TokenType::NativeNear,
+            TokenType::from_account_id(account_id.clone()),
+            TokenType::MultiFungibleToken {
+                account_id: account_id.clone(),
+                subtoken_id: "1".to_string(),
+            },
+            TokenType::MultiFungibleToken {
+                account_id,
+                subtoken_id: "2".to_string(),
+            },
+        ];
+        let mut balances: HashMap<TokenType, u128> = HashMap::new();
+        for (i, token) in tokens.iter().enumerate() {
+            *balances.entry(token.clone()).or_default() += i as u128 + 1;
+        }
+        *balances.entry(TokenType::NativeNear).or_default() += 10;
+
+        assert_eq!(balances.len(), tokens.len());
+        assert_eq!(balances[&tokens[0]], 11);
+        assert_eq!(balances[&tokens[1]], 2);
+        assert_eq!(balances[&tokens[2]], 3);
+        assert_eq!(balances[&tokens[3]], 4);
+    }
+
+    #[test]
+    fn test_validate() {
+        let account_id = AccountId::new_unchecked("token.near".to_string());
+        assert_eq!(TokenType::NativeNear.validate(), Ok(()));
+        assert_eq!(
+            TokenType::from_account_id(account_id.clone()).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            TokenType::MultiFungibleToken {
+                account_id,
+                subtoken_id: "1".to_string(),
+            }
+            .validate(),
+            Ok(())
+        );
+
+        // malformed IDs can't be built in tests, since new_unchecked asserts
+        // validity in debug builds, so check the shared validation directly
+        for id in ["Not A Token", "", "a", "token..near", "-token.near"].iter() {
+            assert_eq!(
+                validate_account_id(id),
+                Err(errors::INVALID_TOKEN_ID),
+                "{}",
+                id
+            );
+        }
+        assert_eq!(validate_account_id("token.near"), Ok(()));
+    }
 }
@@ -0,0 +1,87 @@
+/// 256-bit unsigned integer for intermediate math, with borsh and serde
+/// support so results can be stored.
+#[cfg(feature = "near")]
+use std::io;
+
+#[cfg(feature = "near")]
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "near")]
+use near_sdk::serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+// the expansion of `construct_uint!` trips newer clippy lints
+#[allow(clippy::manual_div_ceil, clippy::assign_op_pattern)]
+mod uint_types {
+    uint::construct_uint! {
+        pub struct U256(4);
+    }
+}
+
+pub use uint_types::U256;
+
+/// Borsh encodes as 32 little-endian bytes.
+#[cfg(feature = "near")]
+impl BorshSerialize for U256 {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut bytes = [0u8; 32];
+        self.to_little_endian(&mut bytes);
+        writer.write_all(&bytes)
+    }
+}
+
+#[cfg(feature = "near")]
+impl BorshDeserialize for U256 {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        let bytes: [u8; 32] = BorshDeserialize::deserialize(buf)?;
+        Ok(U256::from_little_endian(&bytes))
+    }
+}
+
+/// JSON numbers can't hold 256 bits, so serde uses a decimal string.
+#[cfg(feature = "near")]
+impl Serialize for U256 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "near")]
+impl<'de> Deserialize<'de> for U256 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <String as Deserialize>::deserialize(deserializer)?;
+        U256::from_dec_str(&s).map_err(|_| de::Error::custom("invalid U256"))
+    }
+}
+
+#[cfg(all(test, feature = "near"))]
+mod tests {
+    use super::*;
+
+    fn big() -> U256 {
+        U256::from(u128::MAX) * U256::from(1_000u64) + U256::from(7u64)
+    }
+
+    #[test]
+    fn borsh_round_trip() {
+        for v in [U256::zero(), U256::from(u128::MAX), big(), U256::MAX] {
+            let bytes = v.try_to_vec().unwrap();
+            assert_eq!(bytes.len(), 32);
+            assert_eq!(U256::try_from_slice(&bytes).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        for v in [U256::zero(), U256::from(u128::MAX), big(), U256::MAX] {
+            let json = near_sdk::serde_json::to_string(&v).unwrap();
+            assert_eq!(json, format!("\"{}\"", v));
+            let parsed: U256 = near_sdk::serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, v);
+        }
+    }
+
+    #[test]
+    fn serde_rejects_invalid() {
+        assert!(near_sdk::serde_json::from_str::<U256>("\"0x12\"").is_err());
+        assert!(near_sdk::serde_json::from_str::<U256>("12").is_err());
+    }
+}
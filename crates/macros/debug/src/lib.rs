@@ -24,13 +24,10 @@ use syn::{parse_macro_input, ItemFn, Meta};
 /// }
 /// ```
 #[proc_macro_attribute]
-pub fn measure_gas(_cfg_gate: TokenStream, input: TokenStream) -> TokenStream {
+pub fn measure_gas(cfg_gate: TokenStream, input: TokenStream) -> TokenStream {
     let input_fn = parse_macro_input!(input as ItemFn);
-    let fn_attrs = input_fn.attrs;
-    let fn_vis = input_fn.vis;
-    let fn_sig = input_fn.sig;
-    let fn_block = input_fn.block;
-    let fn_name = fn_sig.ident.to_string();
+    let fn_name = input_fn.sig.ident.to_string();
+    let fn_block = &input_fn.block;
 
     let fn_block_with_gas_measurement = quote! {
         let before = near_sdk::env::used_gas().0;
@@ -44,22 +41,75 @@ pub fn measure_gas(_cfg_gate: TokenStream, input: TokenStream) -> TokenStream {
         ret
     };
 
-    if _cfg_gate.is_empty() {
+    wrap_measured(cfg_gate, input_fn, fn_block_with_gas_measurement)
+}
+
+/// Print the net storage usage change of a contract method in bytes,
+/// optionally gated by a cfg attribute. The change is negative if the method
+/// freed storage.
+///
+/// ```ignore
+/// impl Contract {
+///     #[measure_storage(feature = "measure_performance")]
+///     pub fn new_order() {
+///         // storage usage will be logged when the "measure_performance" feature is enabled
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn measure_storage(cfg_gate: TokenStream, input: TokenStream) -> TokenStream {
+    let input_fn = parse_macro_input!(input as ItemFn);
+    let fn_name = input_fn.sig.ident.to_string();
+    let fn_block = &input_fn.block;
+
+    let fn_block_with_storage_measurement = quote! {
+        let before = near_sdk::env::storage_usage();
+
+        let ret = { #fn_block };
+
+        let after = near_sdk::env::storage_usage();
+
+        near_sdk::env::log_str(&format!(
+            "STORAGE USAGE({}): {}",
+            #fn_name,
+            after as i128 - before as i128
+        ));
+
+        ret
+    };
+
+    wrap_measured(cfg_gate, input_fn, fn_block_with_storage_measurement)
+}
+
+/// Replace the body of `input_fn` with `measured_block`, or, if `cfg_gate` is
+/// non-empty, with a body that only runs `measured_block` when the cfg is
+/// enabled.
+fn wrap_measured(
+    cfg_gate: TokenStream,
+    input_fn: ItemFn,
+    measured_block: proc_macro2::TokenStream,
+) -> TokenStream {
+    let fn_attrs = input_fn.attrs;
+    let fn_vis = input_fn.vis;
+    let fn_sig = input_fn.sig;
+    let fn_block = input_fn.block;
+
+    if cfg_gate.is_empty() {
         // always measure
         return proc_macro::TokenStream::from(quote! {
             #(#fn_attrs)*
             #fn_vis #fn_sig {
-                #fn_block_with_gas_measurement
+                #measured_block
             }
         });
     }
     // conditionally measure
-    let cfg_meta = parse_macro_input!(_cfg_gate as Meta);
+    let cfg_meta = parse_macro_input!(cfg_gate as Meta);
     proc_macro::TokenStream::from(quote! {
         #(#fn_attrs)*
         #fn_vis #fn_sig {
             if cfg!(#cfg_meta) {
-                #fn_block_with_gas_measurement
+                #measured_block
             } else {
                 #fn_block
             }
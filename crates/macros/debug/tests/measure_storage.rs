@@ -0,0 +1,49 @@
+use near_sdk::env;
+use near_sdk::test_utils::get_logs;
+use tonic_sdk_macros_debug::measure_storage;
+
+#[measure_storage]
+fn write_entry(key: &[u8], value: &[u8]) -> bool {
+    env::storage_write(key, value)
+}
+
+#[measure_storage]
+fn remove_entry(key: &[u8]) -> bool {
+    env::storage_remove(key)
+}
+
+#[measure_storage(not(test))]
+fn write_entry_gated(key: &[u8], value: &[u8]) -> bool {
+    env::storage_write(key, value)
+}
+
+fn storage_log(name: &str) -> i128 {
+    let prefix = format!("STORAGE USAGE({}): ", name);
+    let logs = get_logs();
+    let log = logs
+        .iter()
+        .find(|l| l.starts_with(&prefix))
+        .unwrap_or_else(|| panic!("no storage log for {}: {:?}", name, logs));
+    log[prefix.len()..].parse().unwrap()
+}
+
+#[test]
+fn logs_storage_delta() {
+    let before = env::storage_usage();
+    assert!(
+        !write_entry(b"key", b"value"),
+        "return value passed through"
+    );
+    let added = storage_log("write_entry");
+    assert_eq!(added, (env::storage_usage() - before) as i128);
+    assert!(added > 0);
+
+    assert!(remove_entry(b"key"));
+    assert_eq!(storage_log("remove_entry"), -added);
+}
+
+#[test]
+fn cfg_gate_disables_logging() {
+    assert!(!write_entry_gated(b"key", b"value"));
+    assert!(get_logs().is_empty());
+}
@@ -11,9 +11,32 @@ pub use tonic_sdk_dex_types as types;
 
 pub mod prelude {
     pub use crate::errors;
+    pub use crate::errors::{match_error, DexError};
     pub use crate::macros;
 
     pub use crate::events::*;
     pub use crate::orderbook::*;
     pub use crate::types::*;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn prelude_market() {
+        let market = Market::new(1_000, 10, 1_000_000);
+        let calc: &OrderbookCalculator = &market.calculator;
+        assert_eq!(calc.base_lot_size, 1_000);
+        assert_eq!(market.best_price_native(Side::Buy), None);
+        assert_eq!(market.midmarket_price_native(), None);
+    }
+
+    #[test]
+    fn prelude_match_error() {
+        assert_eq!(
+            match_error(errors::ORDER_NOT_FOUND),
+            Some(DexError::OrderNotFound)
+        );
+    }
+}